@@ -1,5 +1,7 @@
 use crate::embed::models::AlbumVector;
-use anyhow::{Context, Result};
+use crate::error::Context;
+use crate::error::RequestContext;
+use crate::{GnedbyError, Result};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 
 pub async fn bulk_upload_album_vectors(
@@ -14,9 +16,12 @@ pub async fn bulk_upload_album_vectors(
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     headers.insert(
         AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", token))?,
+        HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid API token")?,
+    );
+    headers.insert(
+        "apikey",
+        HeaderValue::from_str(token).context("Invalid API token")?,
     );
-    headers.insert("apikey", HeaderValue::from_str(token)?);
 
     let res = client
         .post(&url)
@@ -24,12 +29,15 @@ pub async fn bulk_upload_album_vectors(
         .json(vectors)
         .send()
         .await
-        .context("Failed to send bulk request to Supabase")?;
+        .request_context("Failed to send bulk request to Supabase")?;
 
     if !res.status().is_success() {
         let status = res.status();
         let text = res.text().await.unwrap_or_default();
-        anyhow::bail!("Supabase API error (bulk): {} - {}", status, text);
+        return Err(GnedbyError::from_status(
+            status,
+            format!("Supabase API error (bulk): {} - {}", status, text),
+        ));
     }
 
     Ok(())
@@ -46,21 +54,27 @@ pub async fn fetch_embedded_album_ids(api_url: &str, token: &str) -> Result<Vec<
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     headers.insert(
         AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", token))?,
+        HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid API token")?,
+    );
+    headers.insert(
+        "apikey",
+        HeaderValue::from_str(token).context("Invalid API token")?,
     );
-    headers.insert("apikey", HeaderValue::from_str(token)?);
 
     let res = client
         .get(&url)
         .headers(headers)
         .send()
         .await
-        .context("Failed to fetch embedded album ids from Supabase")?;
+        .request_context("Failed to fetch embedded album ids from Supabase")?;
 
     if !res.status().is_success() {
         let status = res.status();
         let text = res.text().await.unwrap_or_default();
-        anyhow::bail!("Supabase API error (fetch ids): {} - {}", status, text);
+        return Err(GnedbyError::from_status(
+            status,
+            format!("Supabase API error (fetch ids): {} - {}", status, text),
+        ));
     }
 
     let ids: Vec<serde_json::Value> = res.json().await?;
@@ -85,9 +99,12 @@ pub async fn update_album_vector(api_url: &str, token: &str, vector: &AlbumVecto
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     headers.insert(
         AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", token))?,
+        HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid API token")?,
+    );
+    headers.insert(
+        "apikey",
+        HeaderValue::from_str(token).context("Invalid API token")?,
     );
-    headers.insert("apikey", HeaderValue::from_str(token)?);
 
     let res = client
         .patch(&url)
@@ -95,12 +112,15 @@ pub async fn update_album_vector(api_url: &str, token: &str, vector: &AlbumVecto
         .json(vector)
         .send()
         .await
-        .context("Failed to send update request to Supabase")?;
+        .request_context("Failed to send update request to Supabase")?;
 
     if !res.status().is_success() {
         let status = res.status();
         let text = res.text().await.unwrap_or_default();
-        anyhow::bail!("Supabase API error (update): {} - {}", status, text);
+        return Err(GnedbyError::from_status(
+            status,
+            format!("Supabase API error (update): {} - {}", status, text),
+        ));
     }
 
     Ok(())
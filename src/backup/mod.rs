@@ -0,0 +1,201 @@
+use crate::config::{load_backup_config, BackupConfig};
+use crate::db::cipher::{apply_key, key_for};
+use crate::db::{get_db_path, open_database_file};
+use crate::error::Context;
+use crate::{GnedbyError, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime, TimeZone, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Timestamp at the start of every backup name, e.g. `20250101-120000`.
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const TIMESTAMP_LEN: usize = 15;
+
+/// Pages copied per step of the online backup, so other connections can keep
+/// using the database while a backup runs.
+const PAGES_PER_STEP: i32 = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct BackupInfo {
+    /// File name without the `.db` extension, used to refer to the backup.
+    pub name: String,
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+    /// Operation the backup was taken before, e.g. "pull" or "delete".
+    pub reason: Option<String>,
+    pub size: u64,
+}
+
+pub fn backups_dir() -> Result<PathBuf> {
+    let db_path = get_db_path()?;
+    let dir = db_path
+        .parent()
+        .ok_or_else(|| GnedbyError::Storage("Failed to determine backup directory".to_string()))?
+        .join("backups");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Copies the live database into a new timestamped backup using SQLite's online
+/// backup API, which gives a consistent snapshot even while the database is in
+/// use. Returns `None` when there is no database yet.
+pub fn create_backup(reason: Option<&str>) -> Result<Option<BackupInfo>> {
+    let db_path = get_db_path()?;
+    if !db_path.exists() {
+        return Ok(None);
+    }
+
+    let path = new_backup_path(Local::now(), reason)?;
+    let source = open_database_file(&db_path)?;
+    let mut destination = Connection::open(&path)?;
+    // An encrypted database is backed up under the same passphrase.
+    if let Some(key) = key_for(&db_path)? {
+        apply_key(&destination, &key)?;
+    }
+    Backup::new(&source, &mut destination)?
+        .run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+        .context("Failed to back up the database")?;
+
+    backup_info(&path).map(Some)
+}
+
+/// Moves a stray copy of the database, such as `albums.db.bak`, into the
+/// backups directory so it can be listed and restored like any other backup.
+/// The backup is named after the time the file was last modified.
+pub fn move_into_backups(file: &Path, reason: &str) -> Result<BackupInfo> {
+    let modified: DateTime<Local> = fs::metadata(file)?.modified()?.into();
+    let path = new_backup_path(modified, Some(reason))?;
+    fs::rename(file, &path)
+        .or_else(|_| fs::copy(file, &path).and_then(|_| fs::remove_file(file)))
+        .context(format!(
+            "Failed to move {} into the backups",
+            file.display()
+        ))?;
+    backup_info(&path)
+}
+
+/// Picks an unused backup file name for a backup taken at `time`.
+fn new_backup_path(time: DateTime<Local>, reason: Option<&str>) -> Result<PathBuf> {
+    let dir = backups_dir()?;
+    let mut name = time.format(TIMESTAMP_FORMAT).to_string();
+    if let Some(reason) = reason {
+        let reason: String = reason
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        name = format!("{}-{}", name, reason);
+    }
+    let mut path = dir.join(format!("{}.db", name));
+    let mut counter = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.db", name, counter));
+        counter += 1;
+    }
+    Ok(path)
+}
+
+/// Creates a backup before a destructive operation when `auto_backup` is
+/// enabled, then prunes old backups according to the retention settings.
+pub fn auto_backup(reason: &str) -> Result<()> {
+    let config = load_backup_config()?;
+    if !config.auto_backup {
+        return Ok(());
+    }
+
+    if let Some(backup) = create_backup(Some(reason))? {
+        println!("Created backup {}", backup.name);
+    }
+    prune_backups(&config)?;
+    Ok(())
+}
+
+/// Lists backups, newest first.
+pub fn list_backups() -> Result<Vec<BackupInfo>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backups_dir()?)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "db") {
+            if let Ok(info) = backup_info(&path) {
+                backups.push(info);
+            }
+        }
+    }
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Replaces the live database with the backup called `name`. The current
+/// database is backed up first, so a restore can itself be undone.
+pub fn restore_backup(name: &str) -> Result<Option<BackupInfo>> {
+    let name = name.trim_end_matches(".db");
+    let backup = list_backups()?
+        .into_iter()
+        .find(|backup| backup.name == name)
+        .ok_or_else(|| GnedbyError::NotFound(format!("Backup \"{}\" not found", name)))?;
+
+    let safety_backup = create_backup(Some("restore"))?;
+    replace_database(&backup.path)?;
+    Ok(safety_backup)
+}
+
+/// Replaces the contents of the database with the database file at `source`.
+/// Going through the backup API keeps the WAL and any other open connections
+/// consistent, unlike copying the file over the database.
+pub fn replace_database(source: &Path) -> Result<()> {
+    let source = open_database_file(source)?;
+    let mut destination = open_database_file(&get_db_path()?)?;
+    Backup::new(&source, &mut destination)?
+        .run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+        .context("Failed to restore the database")?;
+    Ok(())
+}
+
+/// Deletes backups beyond the newest `keep` and those older than
+/// `max_age_days`. The newest backup is always kept. Returns the removed backups.
+pub fn prune_backups(config: &BackupConfig) -> Result<Vec<BackupInfo>> {
+    let cutoff = config
+        .max_age_days
+        .map(|days| Utc::now() - ChronoDuration::days(days.into()));
+
+    let mut removed = Vec::new();
+    for (index, backup) in list_backups()?.into_iter().enumerate() {
+        let too_many = index >= config.keep.max(1);
+        let too_old = index > 0 && cutoff.is_some_and(|cutoff| backup.created < cutoff);
+        if too_many || too_old {
+            fs::remove_file(&backup.path)
+                .context(format!("Failed to remove backup {}", backup.name))?;
+            removed.push(backup);
+        }
+    }
+    Ok(removed)
+}
+
+fn backup_info(path: &Path) -> Result<BackupInfo> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| GnedbyError::Validation(format!("Invalid backup file name: {:?}", path)))?
+        .to_string();
+
+    let timestamp = name
+        .get(..TIMESTAMP_LEN)
+        .and_then(|timestamp| NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok())
+        .and_then(|timestamp| Local.from_local_datetime(&timestamp).earliest())
+        .ok_or_else(|| GnedbyError::Validation(format!("Not a backup file: {:?}", path)))?;
+    let reason = name
+        .get(TIMESTAMP_LEN + 1..)
+        .filter(|reason| !reason.is_empty())
+        .map(str::to_string);
+
+    Ok(BackupInfo {
+        size: fs::metadata(path)?.len(),
+        path: path.to_path_buf(),
+        created: timestamp.with_timezone(&Utc),
+        reason,
+        name,
+    })
+}
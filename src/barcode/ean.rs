@@ -0,0 +1,197 @@
+//! EAN-13 and UPC-A decoding from scan lines of a grayscale image.
+//!
+//! An EAN-13 symbol is 95 modules wide: a `101` start guard, six left digits
+//! of seven modules each, a `01010` middle guard, six right digits and a `101`
+//! end guard. Every digit is two bars and two spaces, so a symbol is 59
+//! alternating runs starting with a bar. The first digit is not drawn; it is
+//! encoded in which of the left digits use odd (L) or even (G) parity.
+
+use image::GrayImage;
+use std::collections::HashMap;
+
+/// Run widths (space, bar, space, bar) of each digit's L code, in modules.
+/// R codes have the same widths starting with a bar; G codes are reversed.
+const L_WIDTHS: [[u8; 4]; 10] = [
+    [3, 2, 1, 1],
+    [2, 2, 2, 1],
+    [2, 1, 2, 2],
+    [1, 4, 1, 1],
+    [1, 1, 3, 2],
+    [1, 2, 3, 1],
+    [1, 1, 1, 4],
+    [1, 3, 1, 2],
+    [1, 2, 1, 3],
+    [3, 1, 1, 2],
+];
+
+/// Parity of the six left digits for each first digit, `true` meaning G.
+const FIRST_DIGIT_PARITY: [[bool; 6]; 10] = [
+    [false, false, false, false, false, false],
+    [false, false, true, false, true, true],
+    [false, false, true, true, false, true],
+    [false, false, true, true, true, false],
+    [false, true, false, false, true, true],
+    [false, true, true, false, false, true],
+    [false, true, true, true, false, false],
+    [false, true, false, true, false, true],
+    [false, true, false, true, true, false],
+    [false, true, true, false, true, false],
+];
+
+const SYMBOL_RUNS: usize = 59;
+const SYMBOL_MODULES: f32 = 95.0;
+
+/// Largest summed difference, in modules, between a digit's measured run
+/// widths and its code before the digit is rejected.
+const MAX_DIGIT_ERROR: f32 = 1.6;
+
+/// Number of rows scanned across the image in each orientation.
+const SCAN_LINES: u32 = 48;
+
+/// Finds an EAN-13 or UPC-A barcode in an image, scanning rows and then
+/// columns in both directions. Returns the digits printed under the barcode:
+/// 12 for UPC-A, otherwise 13.
+pub fn decode(image: &GrayImage) -> Option<String> {
+    let mut found: HashMap<String, usize> = HashMap::new();
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let rows = (0..SCAN_LINES).map(|i| {
+        let y = (height - 1) * (i + 1) / (SCAN_LINES + 1);
+        (0..width)
+            .map(|x| image.get_pixel(x, y)[0])
+            .collect::<Vec<_>>()
+    });
+    let columns = (0..SCAN_LINES).map(|i| {
+        let x = (width - 1) * (i + 1) / (SCAN_LINES + 1);
+        (0..height)
+            .map(|y| image.get_pixel(x, y)[0])
+            .collect::<Vec<_>>()
+    });
+
+    for mut line in rows.chain(columns) {
+        for _ in 0..2 {
+            if let Some(code) = decode_line(&line) {
+                *found.entry(code).or_default() += 1;
+            }
+            line.reverse();
+        }
+    }
+
+    found
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(code, _)| code)
+}
+
+/// Decodes the first symbol on one scan line read left to right.
+fn decode_line(line: &[u8]) -> Option<String> {
+    let runs = runs(line)?;
+    // Runs alternate starting with a bar, so symbols start at even indices.
+    (0..runs.len().saturating_sub(SYMBOL_RUNS - 1))
+        .step_by(2)
+        .find_map(|start| decode_symbol(&runs[start..start + SYMBOL_RUNS]))
+}
+
+/// Splits a scan line into alternating bar and space widths, starting with
+/// the first bar. Returns `None` when the line has too little contrast, or is
+/// too short to hold a symbol.
+fn runs(line: &[u8]) -> Option<Vec<f32>> {
+    if line.len() < SYMBOL_MODULES as usize {
+        return None;
+    }
+    let mut sorted = line.to_vec();
+    sorted.sort_unstable();
+    let dark = sorted[sorted.len() / 20] as u16;
+    let light = sorted[sorted.len() * 19 / 20] as u16;
+    if light - dark < 40 {
+        return None;
+    }
+    let threshold = (dark + light) as f32 / 2.0;
+
+    // Edges are placed where the brightness crosses the threshold, between
+    // pixels, which keeps blurred bars a few pixels wide readable.
+    let mut edges = Vec::new();
+    for (i, pair) in line.windows(2).enumerate() {
+        let (a, b) = (pair[0] as f32, pair[1] as f32);
+        if (a < threshold) != (b < threshold) {
+            edges.push(i as f32 + (threshold - a) / (b - a));
+        }
+    }
+    // Runs start at the first bar, which begins at a light-to-dark edge.
+    let first_bar = usize::from((line[0] as f32) < threshold);
+    let runs: Vec<f32> = edges[first_bar.min(edges.len())..]
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .collect();
+    Some(runs)
+}
+
+fn decode_symbol(runs: &[f32]) -> Option<String> {
+    let module = runs.iter().sum::<f32>() / SYMBOL_MODULES;
+    let is_guard = |widths: &[f32]| {
+        widths
+            .iter()
+            .all(|width| (width / module - 1.0).abs() < 0.6)
+    };
+    if !is_guard(&runs[0..3]) || !is_guard(&runs[27..32]) || !is_guard(&runs[56..59]) {
+        return None;
+    }
+
+    let mut digits = Vec::with_capacity(13);
+    let mut parity = [false; 6];
+    for (i, widths) in runs[3..27].chunks(4).enumerate() {
+        let (digit, even) = decode_digit(widths, true)?;
+        digits.push(digit);
+        parity[i] = even;
+    }
+    for widths in runs[32..56].chunks(4) {
+        digits.push(decode_digit(widths, false)?.0);
+    }
+
+    let first = FIRST_DIGIT_PARITY.iter().position(|p| *p == parity)? as u8;
+    digits.insert(0, first);
+    let code: String = digits.iter().map(|d| char::from(b'0' + d)).collect();
+    if !super::has_valid_check_digit(&code) {
+        return None;
+    }
+    // UPC-A is EAN-13 with a leading zero, which is not printed.
+    Some(match code.strip_prefix('0') {
+        Some(upc) => upc.to_string(),
+        None => code,
+    })
+}
+
+/// Matches four run widths against the digit codes, returning the digit and
+/// whether it used a G (even parity) code. Right-hand digits only use R codes.
+fn decode_digit(widths: &[f32], left: bool) -> Option<(u8, bool)> {
+    let total: f32 = widths.iter().sum();
+    let normalized: Vec<f32> = widths.iter().map(|w| w * 7.0 / total).collect();
+    let error = |code: [u8; 4]| -> f32 {
+        normalized
+            .iter()
+            .zip(code)
+            .map(|(width, modules)| (width - modules as f32).abs())
+            .sum()
+    };
+
+    let mut best: Option<(u8, bool, f32)> = None;
+    for (digit, code) in L_WIDTHS.iter().enumerate() {
+        let mut candidates = vec![(*code, false)];
+        if left {
+            let mut reversed = *code;
+            reversed.reverse();
+            candidates.push((reversed, true));
+        }
+        for (code, even) in candidates {
+            let e = error(code);
+            if best.is_none_or(|(_, _, best)| e < best) {
+                best = Some((digit as u8, even, e));
+            }
+        }
+    }
+    best.filter(|(_, _, e)| *e <= MAX_DIGIT_ERROR)
+        .map(|(digit, even, _)| (digit, even))
+}
@@ -0,0 +1,199 @@
+mod ean;
+
+use crate::{GnedbyError, Result};
+use image::imageops::FilterType;
+use std::path::{Path, PathBuf};
+
+/// Photos are scaled down to this many pixels on their longer side before
+/// scanning, which keeps bars several pixels wide while scanning quickly.
+const MAX_SCAN_SIZE: u32 = 1600;
+
+/// File extensions read by [`image_files`].
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "gif", "tif", "tiff"];
+
+/// Reads an EAN-13 or UPC-A barcode from a photo, returning the printed
+/// digits, or `None` if no barcode could be read. The barcode may be upside
+/// down or turned sideways, but not at an angle.
+pub fn decode_barcode_image(path: &Path) -> Result<Option<String>> {
+    let mut image = image::open(path)?.into_luma8();
+    if image.width().max(image.height()) > MAX_SCAN_SIZE {
+        let scale = MAX_SCAN_SIZE as f32 / image.width().max(image.height()) as f32;
+        image = image::imageops::resize(
+            &image,
+            ((image.width() as f32 * scale) as u32).max(1),
+            ((image.height() as f32 * scale) as u32).max(1),
+            FilterType::Triangle,
+        );
+    }
+    Ok(ean::decode(&image))
+}
+
+/// The image files in `path`, sorted by name, or `path` itself if it is a file.
+pub fn image_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let file = entry?.path();
+        let is_image = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if file.is_file() && is_image {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Strips spaces and dashes from a UPC or EAN barcode and checks its length
+/// and check digit. Accepts EAN-8, UPC-A (12 digits), EAN-13 and GTIN-14.
+pub fn normalize_barcode(input: &str) -> Result<String> {
+    let barcode: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if !barcode.chars().all(|c| c.is_ascii_digit()) {
+        return Err(GnedbyError::Validation(format!(
+            "Barcode may only contain digits: {}",
+            input
+        )));
+    }
+    if ![8, 12, 13, 14].contains(&barcode.len()) {
+        return Err(GnedbyError::Validation(format!(
+            "Barcode must have 8, 12, 13 or 14 digits: {}",
+            input
+        )));
+    }
+    if !has_valid_check_digit(&barcode) {
+        return Err(GnedbyError::Validation(format!(
+            "Barcode check digit does not match: {}",
+            input
+        )));
+    }
+    Ok(barcode)
+}
+
+/// GS1 check digit: digits are weighted 3 and 1 alternately from the right,
+/// excluding the check digit, and the total must round up to a multiple of 10.
+pub(crate) fn has_valid_check_digit(digits: &str) -> bool {
+    let values: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(10)).collect();
+    let Some((check, body)) = values.split_last() else {
+        return false;
+    };
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+    (10 - sum % 10) % 10 == *check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn accepts_upc_and_ean() {
+        assert_eq!(
+            normalize_barcode("0 724384 260927").unwrap(),
+            "0724384260927"
+        );
+        assert_eq!(normalize_barcode("724384260927").unwrap(), "724384260927");
+        assert_eq!(normalize_barcode("8804775079542").unwrap(), "8804775079542");
+    }
+
+    /// Draws `code` as an EAN-13 symbol with `module` pixels per module.
+    fn draw(code: &str, module: u32) -> image::GrayImage {
+        let l = [
+            "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+            "0110111", "0001011",
+        ];
+        let parity = [
+            "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG",
+            "LGLGGL", "LGGLGL",
+        ];
+        let digits: Vec<usize> = code.bytes().map(|b| (b - b'0') as usize).collect();
+        let invert = |p: &str| {
+            p.chars()
+                .map(|c| if c == '0' { '1' } else { '0' })
+                .collect::<String>()
+        };
+
+        let mut bits = String::from("101");
+        for (i, digit) in digits[1..7].iter().enumerate() {
+            if parity[digits[0]].as_bytes()[i] == b'L' {
+                bits.push_str(l[*digit]);
+            } else {
+                bits.extend(invert(l[*digit]).chars().rev());
+            }
+        }
+        bits.push_str("01010");
+        for digit in &digits[7..] {
+            bits.push_str(&invert(l[*digit]));
+        }
+        bits.push_str("101");
+
+        let quiet = 10 * module;
+        let width = bits.len() as u32 * module + 2 * quiet;
+        image::GrayImage::from_fn(width, 60, |x, _| {
+            let bit = x.checked_sub(quiet).map(|x| (x / module) as usize);
+            match bit.and_then(|i| bits.as_bytes().get(i)) {
+                Some(b'1') => image::Luma([20]),
+                _ => image::Luma([235]),
+            }
+        })
+    }
+
+    #[test]
+    fn decodes_ean13_and_upca() {
+        assert_eq!(
+            ean::decode(&draw("8804775079542", 3)).as_deref(),
+            Some("8804775079542")
+        );
+        assert_eq!(
+            ean::decode(&draw("0724384260927", 2)).as_deref(),
+            Some("724384260927")
+        );
+    }
+
+    #[test]
+    fn decodes_turned_barcodes() {
+        let image = draw("8804775079542", 3);
+        let upside_down = image::imageops::rotate180(&image);
+        let sideways = image::imageops::rotate90(&image);
+        assert_eq!(ean::decode(&upside_down).as_deref(), Some("8804775079542"));
+        assert_eq!(ean::decode(&sideways).as_deref(), Some("8804775079542"));
+    }
+
+    #[test]
+    fn blank_image_has_no_barcode() {
+        let image = image::GrayImage::from_pixel(300, 100, image::Luma([255]));
+        assert_eq!(ean::decode(&image), None);
+    }
+
+    #[test]
+    fn degenerate_images_have_no_barcode() {
+        assert_eq!(ean::decode(&image::GrayImage::new(0, 0)), None);
+        assert_eq!(ean::decode(&image::GrayImage::new(300, 0)), None);
+        assert_eq!(ean::decode(&image::GrayImage::new(1, 1)), None);
+
+        // A single row through the barcode is still readable.
+        let strip = image::imageops::crop_imm(&draw("8804775079542", 3), 0, 30, 345, 1).to_image();
+        assert_eq!(ean::decode(&strip).as_deref(), Some("8804775079542"));
+    }
+
+    #[test]
+    fn rejects_bad_barcodes() {
+        for input in ["0724384260928", "12345", "07243842609a7"] {
+            assert_eq!(
+                normalize_barcode(input).unwrap_err().kind(),
+                ErrorKind::Validation
+            );
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use crate::db::models::Format;
 use clap::{ArgGroup, Parser};
+use gnedby::internal::normalize_country;
+use gnedby::Format;
+use std::path::PathBuf;
 
 /// A CLI tool for managing your CD/LP collection
 #[derive(Parser, Debug)]
@@ -11,14 +13,40 @@ pub struct Args {
 
 #[derive(Parser, Debug)]
 pub enum Command {
-    /// Add new albums to your collection using Apple Music album IDs
+    /// Add new albums to your collection by their IDs or URLs at a metadata provider
     Add {
-        /// Apple Music album IDs (e.g., 1811804666 1811804667 1811804668)
+        /// Album IDs or URLs (e.g., Apple Music IDs 1811804666, album, song or share links)
+        #[arg(required_unless_present_any = ["search", "upc", "barcode_image"])]
         album_ids: Vec<String>,
 
-        /// Album format (cd, lp, usb, tape)
-        #[arg(long, default_value = "cd", value_parser = parse_format)]
-        format: Format,
+        /// Search for an album by text (e.g., "artist title") and pick one from the results
+        #[arg(long, conflicts_with = "album_ids")]
+        search: Option<String>,
+
+        /// Look albums up by the UPC/EAN barcodes on their back covers
+        #[arg(long, num_args = 1.., conflicts_with_all = ["album_ids", "search"])]
+        upc: Vec<String>,
+
+        /// Read EAN-13/UPC-A barcodes from a photo, or from every photo in a directory
+        #[arg(long, conflicts_with_all = ["album_ids", "search", "upc"])]
+        barcode_image: Option<PathBuf>,
+
+        /// Metadata provider to look the albums up with (default from `gnedby provider config`)
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// Apple Music storefront to look up and search in (e.g., kr, jp; default from config)
+        #[arg(long, value_parser = parse_country)]
+        store: Option<String>,
+
+        /// Album format (cd, lp, usb, tape); defaults to the release's format
+        /// when the provider knows it, otherwise cd
+        #[arg(long, value_parser = parse_format)]
+        format: Option<Format>,
+
+        /// Country the artist or release comes from (e.g., KR, Korea, Japan)
+        #[arg(long, value_parser = parse_country)]
+        origin: Option<String>,
     },
     /// Add album manually with interactive prompts
     ManualAdd {
@@ -60,13 +88,46 @@ pub enum Command {
         #[arg(long, value_parser = parse_format)]
         format: Option<Format>,
 
-        /// Filter albums by country
-        #[arg(long)]
+        /// Filter albums by country of origin (e.g., KR, Korea, Japan)
+        #[arg(long, value_parser = parse_country)]
         country: Option<String>,
 
         /// Order results by field (id, album, artist, year)
         #[arg(long, default_value = "id")]
         order_by: Option<String>,
+
+        /// Show at most this many albums
+        #[arg(long)]
+        limit: Option<u32>,
+
+        /// Skip this many albums before listing
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+
+        /// Print directly instead of through $PAGER
+        #[arg(long, default_value_t = false)]
+        no_pager: bool,
+    },
+    /// Search albums by artist or title, in any script (e.g., "seo taiji" finds 서태지)
+    Search {
+        /// Text to search for
+        query: String,
+
+        /// Order results by field (id, album, artist, year)
+        #[arg(long, default_value = "id")]
+        order_by: Option<String>,
+
+        /// Show at most this many albums
+        #[arg(long)]
+        limit: Option<u32>,
+
+        /// Skip this many albums before listing
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+
+        /// Print directly instead of through $PAGER
+        #[arg(long, default_value_t = false)]
+        no_pager: bool,
     },
     /// Generate a summary report of your collection
     #[command(group(
@@ -88,14 +149,72 @@ pub enum Command {
         #[arg(long)]
         genre: bool,
 
+        /// Roll sub-genres up into their top-level genre (use with --genre)
+        #[arg(long, requires = "genre")]
+        top_level: bool,
+
         /// Filter report by format (cd, lp, usb, tape)
         #[arg(long)]
         format: bool,
 
-        /// Filter report by country
+        /// Filter report by country of origin
         #[arg(long)]
         country: bool,
     },
+    /// Show the track listing of an album
+    Tracks {
+        /// Album ID
+        id: i64,
+
+        /// Re-fetch the track listing from the album's metadata provider
+        #[arg(long, default_value_t = false)]
+        refresh: bool,
+    },
+    /// Re-fetch albums from their metadata provider and review the changes
+    Refresh {
+        /// Album IDs to refresh
+        #[arg(required_unless_present = "all")]
+        ids: Vec<i64>,
+
+        /// Refresh every album added from a provider
+        #[arg(long, default_value_t = false, conflicts_with = "ids")]
+        all: bool,
+
+        /// Apply every change without asking
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+    },
+    /// Find which albums in your collection contain a song
+    Has {
+        /// Song title (partial match)
+        title: String,
+    },
+    /// Manage genres, aliases and the genre hierarchy
+    Genre {
+        #[command(subcommand)]
+        command: GenreCommand,
+    },
+    /// Check the database for problems and repair them
+    Doctor {
+        /// Apply the suggested repairs without asking
+        #[arg(long, default_value_t = false)]
+        fix: bool,
+    },
+    /// Create, list and restore local database backups
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Manage the local database file
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// List and configure metadata providers
+    Provider {
+        #[command(subcommand)]
+        command: ProviderCommand,
+    },
     /// Synchronize your collection with remote storage
     Sync {
         #[command(subcommand)]
@@ -105,6 +224,105 @@ pub enum Command {
     Serve,
 }
 
+#[derive(Parser, Debug)]
+pub enum GenreCommand {
+    /// List genres with their parent and album count
+    List,
+    /// Map an alternative spelling onto a genre, merging them if both exist
+    Alias {
+        /// Alternative spelling (e.g., kpop)
+        alias: String,
+        /// Genre to map it to (e.g., K-Pop)
+        genre: String,
+    },
+    /// Nest a genre under a parent genre
+    Parent {
+        /// Genre to move (e.g., K-Pop)
+        genre: String,
+        /// Parent genre (e.g., Pop); omit to make it top-level
+        parent: Option<String>,
+    },
+    /// Replace the genres of an album
+    Set {
+        /// Album ID
+        id: i64,
+        /// One or more genres
+        #[arg(required = true)]
+        genres: Vec<String>,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum BackupCommand {
+    /// Back up the database now
+    Create,
+    /// List backups, newest first
+    List,
+    /// Replace the database with a backup (the current database is backed up first)
+    Restore {
+        /// Backup name as shown by `gnedby backup list`
+        name: String,
+    },
+    /// Delete backups according to the retention settings
+    Prune,
+    /// Configure backup settings
+    Config {
+        #[command(subcommand)]
+        command: BackupConfigCommand,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum BackupConfigCommand {
+    /// Show current backup configuration
+    Show,
+    /// Set a backup configuration value (auto_backup, keep, max_age_days)
+    Set {
+        /// Configuration key
+        key: String,
+        /// Configuration value
+        value: String,
+    },
+    /// Reset backup configuration to default values
+    Reset,
+}
+
+#[derive(Parser, Debug)]
+pub enum ProviderCommand {
+    /// List metadata providers and whether they are enabled
+    List,
+    /// Configure metadata providers
+    Config {
+        #[command(subcommand)]
+        command: ProviderConfigCommand,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum ProviderConfigCommand {
+    /// Show current provider configuration
+    Show,
+    /// Set a provider configuration value (default_provider, providers,
+    /// discogs_token, apple_store, apple_lang, artwork_size)
+    Set {
+        /// Configuration key
+        key: String,
+        /// Configuration value (providers takes a comma-separated list,
+        /// artwork_size a pixel size from 100 to 3000)
+        value: String,
+    },
+    /// Reset provider configuration to default values
+    Reset,
+}
+
+#[derive(Parser, Debug)]
+pub enum DbCommand {
+    /// Encrypt the database with a passphrase (needs the `sqlcipher` feature)
+    Encrypt,
+    /// Remove the database encryption
+    Decrypt,
+}
+
 #[derive(Parser, Debug)]
 pub enum SyncCommand {
     /// Check if your local collection is in sync with remote
@@ -172,10 +390,21 @@ pub enum EmbedConfigCommand {
 }
 
 fn parse_format(s: &str) -> Result<Format, String> {
-    Format::from_str(s).ok_or_else(|| {
+    Format::parse(s).ok_or_else(|| {
         format!(
             "Invalid format: {}. Valid formats are: cd, lp, usb, tape",
             s
         )
     })
 }
+
+fn parse_country(s: &str) -> Result<String, String> {
+    normalize_country(s)
+        .map(|c| c.alpha2.to_string())
+        .ok_or_else(|| {
+            format!(
+                "Unknown country: {}. Use an ISO code (e.g., KR, JPN) or a name (e.g., Korea)",
+                s
+            )
+        })
+}
@@ -1,5 +1,7 @@
 mod commands;
+mod pager;
 mod parser;
 
 pub use commands::*;
+pub use pager::*;
 pub use parser::*;
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Prints `text`, piping it through `$PAGER` (or `less`) when stdout is a
+/// terminal. Like git, `less` is started with `LESS=FRX` unless the user has
+/// set `LESS`, so output that fits on one screen is printed as usual.
+pub fn page_output(text: &str, no_pager: bool) -> Result<()> {
+    if no_pager || !io::stdout().is_terminal() {
+        print!("{}", text);
+        return Ok(());
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next().filter(|program| *program != "cat") else {
+        print!("{}", text);
+        return Ok(());
+    };
+
+    let mut command = Command::new(program);
+    command.args(parts).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    let Ok(mut child) = command.spawn() else {
+        print!("{}", text);
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may quit before reading everything, which is not an error.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
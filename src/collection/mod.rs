@@ -0,0 +1,232 @@
+//! Adding albums from metadata providers and refreshing them later. The CLI
+//! only asks questions and prints; the steps themselves live here.
+
+use crate::barcode::{decode_barcode_image, image_files};
+use crate::db::{Album, Database, FieldChange, Format, Track};
+use crate::metadata::{source_lookup_id, AlbumMetadata, MetadataProvider, ProviderRegistry};
+use crate::{GnedbyError, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How albums found by a provider are stored.
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+    /// Format of the copy in the collection. Defaults to the format the
+    /// provider reports, or CD.
+    pub format: Option<Format>,
+    /// Origin country (ISO alpha-2) to record instead of the provider's.
+    pub origin: Option<String>,
+}
+
+/// Stores an album found by a provider, with its tracks, and returns it with
+/// its new ID.
+pub async fn add_metadata(
+    db: &Database,
+    metadata: AlbumMetadata,
+    options: &AddOptions,
+) -> Result<Album> {
+    let format = options.format.or(metadata.format).unwrap_or(Format::Cd);
+    let (mut album, tracks) = metadata.into_album(format);
+    if let Some(origin) = &options.origin {
+        album.origin_country = Some(origin.clone());
+    }
+
+    let id = db.add_album(&album).await?;
+    db.replace_tracks(id, &tracks).await?;
+    album.id = Some(id);
+    Ok(album)
+}
+
+/// Fetches the album given to `gnedby add` as an id or URL, with `provider`
+/// if given, otherwise with the provider that recognizes the URL or the
+/// default provider.
+pub async fn lookup_input(
+    registry: &ProviderRegistry,
+    provider: Option<&Arc<dyn MetadataProvider>>,
+    input: &str,
+) -> Result<AlbumMetadata> {
+    let (provider, id) = match provider {
+        Some(provider) => (
+            provider.clone(),
+            provider
+                .id_from_url(input)
+                .unwrap_or_else(|| input.to_string()),
+        ),
+        None => registry.resolve(input)?,
+    };
+    provider.lookup(&id).await
+}
+
+/// Searches `provider`, failing with `NotFound` when nothing matches.
+pub async fn search(provider: &dyn MetadataProvider, query: &str) -> Result<Vec<AlbumMetadata>> {
+    let results = provider.search(query).await?;
+    if results.is_empty() {
+        return Err(GnedbyError::NotFound(format!(
+            "No albums found for \"{}\"",
+            query
+        )));
+    }
+    Ok(results)
+}
+
+/// Looks a search result up in full, since results may come without tracks.
+/// Apple results are looked up in the storefront they were found in.
+pub async fn lookup_search_result(
+    provider: &dyn MetadataProvider,
+    result: &AlbumMetadata,
+) -> Result<AlbumMetadata> {
+    let id = source_lookup_id(
+        &result.provider,
+        &result.source_id,
+        result.storefront.as_deref(),
+    );
+    provider.lookup(&id).await
+}
+
+/// Finds the album with `barcode` through the provider chain, failing with
+/// `NotFound` when no provider knows it.
+pub async fn lookup_barcode(
+    registry: &ProviderRegistry,
+    provider: Option<&Arc<dyn MetadataProvider>>,
+    barcode: &str,
+) -> Result<AlbumMetadata> {
+    registry
+        .lookup_barcode(barcode, provider)
+        .await?
+        .ok_or_else(|| GnedbyError::NotFound(format!("No album found with barcode: {}", barcode)))
+}
+
+/// Outcome of [`add_from_barcode_images`].
+#[derive(Debug, Default)]
+pub struct BarcodeImageReport {
+    /// Number of images read.
+    pub images: usize,
+    pub added: Vec<Album>,
+    /// Images that were not added, with the reason.
+    pub misses: Vec<(PathBuf, String)>,
+}
+
+/// Reads the barcode in each photo at `path`, a file or a directory, and adds
+/// the albums found. A photo that fails is recorded in the report rather than
+/// stopping the others.
+pub async fn add_from_barcode_images(
+    db: &Database,
+    registry: &ProviderRegistry,
+    provider: Option<&Arc<dyn MetadataProvider>>,
+    path: &Path,
+    options: &AddOptions,
+) -> Result<BarcodeImageReport> {
+    let files = image_files(path)?;
+    let mut report = BarcodeImageReport {
+        images: files.len(),
+        ..Default::default()
+    };
+
+    for file in files {
+        let barcode = match decode_barcode_image(&file) {
+            Ok(Some(barcode)) => barcode,
+            Ok(None) => {
+                report.misses.push((file, "no barcode found".to_string()));
+                continue;
+            }
+            Err(e) => {
+                report.misses.push((file, e.to_string()));
+                continue;
+            }
+        };
+        let result = match registry.lookup_barcode(&barcode, provider).await {
+            Ok(Some(metadata)) => add_metadata(db, metadata, options).await,
+            Ok(None) => {
+                report
+                    .misses
+                    .push((file, format!("no album found with barcode {}", barcode)));
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(album) => report.added.push(album),
+            Err(e) => report
+                .misses
+                .push((file, format!("barcode {}: {}", barcode, e))),
+        }
+    }
+    Ok(report)
+}
+
+/// Differences between a stored album and what its provider reports now.
+#[derive(Debug, Clone)]
+pub struct Refresh {
+    pub album: Album,
+    pub fresh: Album,
+    /// Changed fields, leaving out fields edited by hand.
+    pub changes: Vec<FieldChange>,
+    pub stored_tracks: Vec<Track>,
+    pub fresh_tracks: Vec<Track>,
+}
+
+impl Refresh {
+    /// Whether the provider has a track listing that differs from the stored one.
+    pub fn tracks_changed(&self) -> bool {
+        !self.fresh_tracks.is_empty() && self.fresh_tracks != self.stored_tracks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.tracks_changed()
+    }
+
+    /// The album with the accepted changes applied. A new artist brings the
+    /// provider's artist credits along.
+    pub fn updated_album(&self, accepted: &[&FieldChange]) -> Album {
+        let mut album = self.album.clone();
+        for change in accepted {
+            album.set_field(change.field, change.new.clone());
+            if change.field == "artist" {
+                album.artists = self.fresh.artists.clone();
+            }
+        }
+        album
+    }
+}
+
+/// Looks `album` up again at its provider and compares it with the stored
+/// copy. Returns `None` for albums that were not added from a provider.
+pub async fn check_refresh(
+    db: &Database,
+    registry: &ProviderRegistry,
+    album: &Album,
+) -> Result<Option<Refresh>> {
+    let Some(metadata) = registry.lookup_album(album).await? else {
+        return Ok(None);
+    };
+    let (fresh, fresh_tracks) = metadata.into_album(album.format);
+    let id = album
+        .id
+        .ok_or_else(|| GnedbyError::Validation("Album has no ID".to_string()))?;
+    Ok(Some(Refresh {
+        changes: album.changes_from(&fresh),
+        stored_tracks: db.get_tracks(id).await?,
+        album: album.clone(),
+        fresh,
+        fresh_tracks,
+    }))
+}
+
+/// Stores the accepted changes of a refresh, and the provider's track listing
+/// when `replace_tracks` is set. Returns whether anything was written.
+pub async fn apply_refresh(
+    db: &Database,
+    refresh: &Refresh,
+    accepted: &[&FieldChange],
+    replace_tracks: bool,
+) -> Result<bool> {
+    let replace_tracks = replace_tracks && refresh.tracks_changed();
+    if !accepted.is_empty() {
+        db.update_album(&refresh.updated_album(accepted)).await?;
+    }
+    if replace_tracks {
+        let id = refresh.album.id.unwrap_or_default();
+        db.replace_tracks(id, &refresh.fresh_tracks).await?;
+    }
+    Ok(!accepted.is_empty() || replace_tracks)
+}
@@ -1,3 +1,4 @@
+use crate::metadata::DEFAULT_ARTWORK_SIZE;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -13,3 +14,55 @@ pub struct EmbedConfig {
     pub api_url: Option<String>,
     pub token: Option<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Create a backup before destructive operations such as delete or sync pull.
+    pub auto_backup: bool,
+    /// Number of most recent backups kept by `gnedby backup prune`.
+    pub keep: usize,
+    /// Backups older than this many days are pruned, if set.
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            auto_backup: true,
+            keep: 10,
+            max_age_days: None,
+        }
+    }
+}
+
+/// Metadata providers used by `gnedby add`, by name.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MetadataConfig {
+    /// Provider used when `--provider` is not given.
+    pub default_provider: String,
+    /// Enabled providers, in the order they are tried when looking up a barcode.
+    pub providers: Vec<String>,
+    /// Discogs personal access token, needed for searches.
+    pub discogs_token: Option<String>,
+    /// Apple Music storefront used when an album URL names none, e.g. "kr".
+    pub apple_store: Option<String>,
+    /// Language of Apple Music titles, e.g. "ko_kr", "ja_jp" or "en_us".
+    pub apple_lang: Option<String>,
+    /// Width and height of Apple Music artwork, in pixels.
+    pub artwork_size: u32,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        MetadataConfig {
+            default_provider: "apple".to_string(),
+            providers: vec!["apple".to_string(), "bandcamp".to_string()],
+            discogs_token: None,
+            apple_store: None,
+            apple_lang: None,
+            artwork_size: DEFAULT_ARTWORK_SIZE,
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use crate::config::models::{EmbedConfig, SyncConfig};
-use anyhow::{Context, Result};
+use crate::config::models::{BackupConfig, EmbedConfig, MetadataConfig, SyncConfig};
+use crate::error::Context;
+use crate::{GnedbyError, Result};
 use base64::{engine::general_purpose, Engine as _};
 use directories::ProjectDirs;
 use serde::{de::DeserializeOwned, Serialize};
@@ -11,7 +12,7 @@ const ENCRYPTION_SALT: &[u8] = b"GNEDBY_TOKEN_ENCRYPTION_SALT";
 
 fn get_config_path(config_name: &str) -> Result<PathBuf> {
     let project_dirs = ProjectDirs::from("com", "gnedby", "gnedby")
-        .ok_or_else(|| anyhow::anyhow!("Failed to determine config directory"))?;
+        .ok_or_else(|| GnedbyError::Storage("Failed to determine config directory".to_string()))?;
     let config_dir = project_dirs.config_dir();
     fs::create_dir_all(config_dir)?;
     Ok(config_dir.join(format!("{}.json", config_name)))
@@ -19,7 +20,7 @@ fn get_config_path(config_name: &str) -> Result<PathBuf> {
 
 fn get_encryption_key() -> Result<Vec<u8>> {
     let project_dirs = ProjectDirs::from("com", "gnedby", "gnedby")
-        .ok_or_else(|| anyhow::anyhow!("Failed to determine config directory"))?;
+        .ok_or_else(|| GnedbyError::Storage("Failed to determine config directory".to_string()))?;
     let config_path = project_dirs.config_dir().to_string_lossy().to_string();
     let mut hasher = Sha256::new();
     hasher.update(config_path.as_bytes());
@@ -50,8 +51,10 @@ fn decrypt_token(encrypted: &str) -> Result<String> {
 }
 
 pub trait TokenConfig {
-    fn get_token(&self) -> Option<&str>;
-    fn set_token(&mut self, token: String);
+    fn get_token(&self) -> Option<&str> {
+        None
+    }
+    fn set_token(&mut self, _token: String) {}
 }
 
 impl TokenConfig for SyncConfig {
@@ -72,6 +75,17 @@ impl TokenConfig for EmbedConfig {
     }
 }
 
+impl TokenConfig for BackupConfig {}
+
+impl TokenConfig for MetadataConfig {
+    fn get_token(&self) -> Option<&str> {
+        self.discogs_token.as_deref()
+    }
+    fn set_token(&mut self, token: String) {
+        self.discogs_token = Some(token);
+    }
+}
+
 fn load_config<T: DeserializeOwned + Default + TokenConfig>(config_name: &str) -> Result<T> {
     let config_path = get_config_path(config_name)?;
     if !config_path.exists() {
@@ -120,3 +134,15 @@ pub fn load_embed_config() -> Result<EmbedConfig> {
 pub fn save_embed_config(config: &EmbedConfig) -> Result<()> {
     save_config(config, "embed_config")
 }
+pub fn load_backup_config() -> Result<BackupConfig> {
+    load_config("backup_config")
+}
+pub fn save_backup_config(config: &BackupConfig) -> Result<()> {
+    save_config(config, "backup_config")
+}
+pub fn load_metadata_config() -> Result<MetadataConfig> {
+    load_config("metadata_config")
+}
+pub fn save_metadata_config(config: &MetadataConfig) -> Result<()> {
+    save_config(config, "metadata_config")
+}
@@ -0,0 +1,433 @@
+//! ISO 3166-1 country codes and the free-text spellings people use for them.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Country {
+    pub alpha2: &'static str,
+    pub alpha3: &'static str,
+    pub name: &'static str,
+}
+
+impl Country {
+    const fn new(alpha2: &'static str, alpha3: &'static str, name: &'static str) -> Self {
+        Country {
+            alpha2,
+            alpha3,
+            name,
+        }
+    }
+}
+
+/// Resolves an ISO alpha-2/alpha-3 code, a country name or a common spelling
+/// ("Korea", "UK", "한국", "U.S.A.") to its country.
+pub fn normalize_country(input: &str) -> Option<&'static Country> {
+    let key = country_key(input);
+    if key.is_empty() {
+        return None;
+    }
+
+    let by_code = |code: &str| {
+        COUNTRIES
+            .iter()
+            .find(|c| c.alpha2.eq_ignore_ascii_case(code))
+    };
+
+    COUNTRIES
+        .iter()
+        .find(|c| {
+            c.alpha2.eq_ignore_ascii_case(&key)
+                || c.alpha3.eq_ignore_ascii_case(&key)
+                || country_key(c.name) == key
+        })
+        .or_else(|| {
+            ALIASES
+                .iter()
+                .find(|(alias, _)| country_key(alias) == key)
+                .and_then(|(_, code)| by_code(code))
+        })
+}
+
+/// Returns the display name for a stored alpha-2 code, falling back to the
+/// code itself for values that predate normalization.
+pub fn country_name(code: &str) -> &str {
+    normalize_country(code).map(|c| c.name).unwrap_or(code)
+}
+
+fn country_key(input: &str) -> String {
+    input
+        .trim()
+        .to_lowercase()
+        .replace('.', "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const ALIASES: &[(&str, &str)] = &[
+    ("korea", "KR"),
+    ("republic of korea", "KR"),
+    ("한국", "KR"),
+    ("대한민국", "KR"),
+    ("韓国", "KR"),
+    ("america", "US"),
+    ("united states", "US"),
+    ("미국", "US"),
+    ("uk", "GB"),
+    ("britain", "GB"),
+    ("great britain", "GB"),
+    ("england", "GB"),
+    ("scotland", "GB"),
+    ("wales", "GB"),
+    ("northern ireland", "GB"),
+    ("영국", "GB"),
+    ("일본", "JP"),
+    ("日本", "JP"),
+    ("중국", "CN"),
+    ("中国", "CN"),
+    ("대만", "TW"),
+    ("台湾", "TW"),
+    ("홍콩", "HK"),
+    ("캐나다", "CA"),
+    ("호주", "AU"),
+    ("프랑스", "FR"),
+    ("독일", "DE"),
+    ("스웨덴", "SE"),
+    ("아이슬란드", "IS"),
+    ("holland", "NL"),
+    ("the netherlands", "NL"),
+    ("czech republic", "CZ"),
+    ("ivory coast", "CI"),
+    ("cote d'ivoire", "CI"),
+    ("turkiye", "TR"),
+    ("türkiye", "TR"),
+    ("macedonia", "MK"),
+    ("cape verde", "CV"),
+    ("east timor", "TL"),
+    ("burma", "MM"),
+    ("vatican", "VA"),
+    ("west germany", "DE"),
+    ("east germany", "DE"),
+    ("ussr", "RU"),
+    ("soviet union", "RU"),
+    ("brunei darussalam", "BN"),
+    ("bolivia (plurinational state of)", "BO"),
+    ("cocos (keeling) islands", "CC"),
+    ("congo (democratic republic of the)", "CD"),
+    ("falkland islands (malvinas)", "FK"),
+    ("micronesia (federated states of)", "FM"),
+    ("united kingdom of great britain and northern ireland", "GB"),
+    ("iran (islamic republic of)", "IR"),
+    ("korea (democratic people's republic of)", "KP"),
+    ("korea (republic of)", "KR"),
+    ("lao people's democratic republic", "LA"),
+    ("moldova (republic of)", "MD"),
+    ("saint martin (french part)", "MF"),
+    ("macedonia (the former yugoslav republic of)", "MK"),
+    ("palestine, state of", "PS"),
+    ("russian federation", "RU"),
+    ("saint helena, ascension and tristan da cunha", "SH"),
+    ("sint maarten (dutch part)", "SX"),
+    ("syrian arab republic", "SY"),
+    ("swaziland", "SZ"),
+    ("taiwan, province of china", "TW"),
+    ("tanzania, united republic of", "TZ"),
+    ("united states of america", "US"),
+    ("holy see", "VA"),
+    ("venezuela (bolivarian republic of)", "VE"),
+    ("virgin islands (british)", "VG"),
+    ("virgin islands (u.s.)", "VI"),
+    ("viet nam", "VN"),
+];
+
+/// ISO 3166-1 countries with short display names.
+pub const COUNTRIES: &[Country] = &[
+    Country::new("AD", "AND", "Andorra"),
+    Country::new("AE", "ARE", "United Arab Emirates"),
+    Country::new("AF", "AFG", "Afghanistan"),
+    Country::new("AG", "ATG", "Antigua and Barbuda"),
+    Country::new("AI", "AIA", "Anguilla"),
+    Country::new("AL", "ALB", "Albania"),
+    Country::new("AM", "ARM", "Armenia"),
+    Country::new("AO", "AGO", "Angola"),
+    Country::new("AQ", "ATA", "Antarctica"),
+    Country::new("AR", "ARG", "Argentina"),
+    Country::new("AS", "ASM", "American Samoa"),
+    Country::new("AT", "AUT", "Austria"),
+    Country::new("AU", "AUS", "Australia"),
+    Country::new("AW", "ABW", "Aruba"),
+    Country::new("AX", "ALA", "Åland Islands"),
+    Country::new("AZ", "AZE", "Azerbaijan"),
+    Country::new("BA", "BIH", "Bosnia and Herzegovina"),
+    Country::new("BB", "BRB", "Barbados"),
+    Country::new("BD", "BGD", "Bangladesh"),
+    Country::new("BE", "BEL", "Belgium"),
+    Country::new("BF", "BFA", "Burkina Faso"),
+    Country::new("BG", "BGR", "Bulgaria"),
+    Country::new("BH", "BHR", "Bahrain"),
+    Country::new("BI", "BDI", "Burundi"),
+    Country::new("BJ", "BEN", "Benin"),
+    Country::new("BL", "BLM", "Saint Barthélemy"),
+    Country::new("BM", "BMU", "Bermuda"),
+    Country::new("BN", "BRN", "Brunei"),
+    Country::new("BO", "BOL", "Bolivia"),
+    Country::new("BQ", "BES", "Bonaire, Sint Eustatius and Saba"),
+    Country::new("BR", "BRA", "Brazil"),
+    Country::new("BS", "BHS", "Bahamas"),
+    Country::new("BT", "BTN", "Bhutan"),
+    Country::new("BV", "BVT", "Bouvet Island"),
+    Country::new("BW", "BWA", "Botswana"),
+    Country::new("BY", "BLR", "Belarus"),
+    Country::new("BZ", "BLZ", "Belize"),
+    Country::new("CA", "CAN", "Canada"),
+    Country::new("CC", "CCK", "Cocos Islands"),
+    Country::new("CD", "COD", "DR Congo"),
+    Country::new("CF", "CAF", "Central African Republic"),
+    Country::new("CG", "COG", "Congo"),
+    Country::new("CH", "CHE", "Switzerland"),
+    Country::new("CI", "CIV", "Côte d'Ivoire"),
+    Country::new("CK", "COK", "Cook Islands"),
+    Country::new("CL", "CHL", "Chile"),
+    Country::new("CM", "CMR", "Cameroon"),
+    Country::new("CN", "CHN", "China"),
+    Country::new("CO", "COL", "Colombia"),
+    Country::new("CR", "CRI", "Costa Rica"),
+    Country::new("CU", "CUB", "Cuba"),
+    Country::new("CV", "CPV", "Cabo Verde"),
+    Country::new("CW", "CUW", "Curaçao"),
+    Country::new("CX", "CXR", "Christmas Island"),
+    Country::new("CY", "CYP", "Cyprus"),
+    Country::new("CZ", "CZE", "Czechia"),
+    Country::new("DE", "DEU", "Germany"),
+    Country::new("DJ", "DJI", "Djibouti"),
+    Country::new("DK", "DNK", "Denmark"),
+    Country::new("DM", "DMA", "Dominica"),
+    Country::new("DO", "DOM", "Dominican Republic"),
+    Country::new("DZ", "DZA", "Algeria"),
+    Country::new("EC", "ECU", "Ecuador"),
+    Country::new("EE", "EST", "Estonia"),
+    Country::new("EG", "EGY", "Egypt"),
+    Country::new("EH", "ESH", "Western Sahara"),
+    Country::new("ER", "ERI", "Eritrea"),
+    Country::new("ES", "ESP", "Spain"),
+    Country::new("ET", "ETH", "Ethiopia"),
+    Country::new("FI", "FIN", "Finland"),
+    Country::new("FJ", "FJI", "Fiji"),
+    Country::new("FK", "FLK", "Falkland Islands"),
+    Country::new("FM", "FSM", "Micronesia"),
+    Country::new("FO", "FRO", "Faroe Islands"),
+    Country::new("FR", "FRA", "France"),
+    Country::new("GA", "GAB", "Gabon"),
+    Country::new("GB", "GBR", "United Kingdom"),
+    Country::new("GD", "GRD", "Grenada"),
+    Country::new("GE", "GEO", "Georgia"),
+    Country::new("GF", "GUF", "French Guiana"),
+    Country::new("GG", "GGY", "Guernsey"),
+    Country::new("GH", "GHA", "Ghana"),
+    Country::new("GI", "GIB", "Gibraltar"),
+    Country::new("GL", "GRL", "Greenland"),
+    Country::new("GM", "GMB", "Gambia"),
+    Country::new("GN", "GIN", "Guinea"),
+    Country::new("GP", "GLP", "Guadeloupe"),
+    Country::new("GQ", "GNQ", "Equatorial Guinea"),
+    Country::new("GR", "GRC", "Greece"),
+    Country::new("GS", "SGS", "South Georgia and the South Sandwich Islands"),
+    Country::new("GT", "GTM", "Guatemala"),
+    Country::new("GU", "GUM", "Guam"),
+    Country::new("GW", "GNB", "Guinea-Bissau"),
+    Country::new("GY", "GUY", "Guyana"),
+    Country::new("HK", "HKG", "Hong Kong"),
+    Country::new("HM", "HMD", "Heard Island and McDonald Islands"),
+    Country::new("HN", "HND", "Honduras"),
+    Country::new("HR", "HRV", "Croatia"),
+    Country::new("HT", "HTI", "Haiti"),
+    Country::new("HU", "HUN", "Hungary"),
+    Country::new("ID", "IDN", "Indonesia"),
+    Country::new("IE", "IRL", "Ireland"),
+    Country::new("IL", "ISR", "Israel"),
+    Country::new("IM", "IMN", "Isle of Man"),
+    Country::new("IN", "IND", "India"),
+    Country::new("IO", "IOT", "British Indian Ocean Territory"),
+    Country::new("IQ", "IRQ", "Iraq"),
+    Country::new("IR", "IRN", "Iran"),
+    Country::new("IS", "ISL", "Iceland"),
+    Country::new("IT", "ITA", "Italy"),
+    Country::new("JE", "JEY", "Jersey"),
+    Country::new("JM", "JAM", "Jamaica"),
+    Country::new("JO", "JOR", "Jordan"),
+    Country::new("JP", "JPN", "Japan"),
+    Country::new("KE", "KEN", "Kenya"),
+    Country::new("KG", "KGZ", "Kyrgyzstan"),
+    Country::new("KH", "KHM", "Cambodia"),
+    Country::new("KI", "KIR", "Kiribati"),
+    Country::new("KM", "COM", "Comoros"),
+    Country::new("KN", "KNA", "Saint Kitts and Nevis"),
+    Country::new("KP", "PRK", "North Korea"),
+    Country::new("KR", "KOR", "South Korea"),
+    Country::new("KW", "KWT", "Kuwait"),
+    Country::new("KY", "CYM", "Cayman Islands"),
+    Country::new("KZ", "KAZ", "Kazakhstan"),
+    Country::new("LA", "LAO", "Laos"),
+    Country::new("LB", "LBN", "Lebanon"),
+    Country::new("LC", "LCA", "Saint Lucia"),
+    Country::new("LI", "LIE", "Liechtenstein"),
+    Country::new("LK", "LKA", "Sri Lanka"),
+    Country::new("LR", "LBR", "Liberia"),
+    Country::new("LS", "LSO", "Lesotho"),
+    Country::new("LT", "LTU", "Lithuania"),
+    Country::new("LU", "LUX", "Luxembourg"),
+    Country::new("LV", "LVA", "Latvia"),
+    Country::new("LY", "LBY", "Libya"),
+    Country::new("MA", "MAR", "Morocco"),
+    Country::new("MC", "MCO", "Monaco"),
+    Country::new("MD", "MDA", "Moldova"),
+    Country::new("ME", "MNE", "Montenegro"),
+    Country::new("MF", "MAF", "Saint Martin"),
+    Country::new("MG", "MDG", "Madagascar"),
+    Country::new("MH", "MHL", "Marshall Islands"),
+    Country::new("MK", "MKD", "North Macedonia"),
+    Country::new("ML", "MLI", "Mali"),
+    Country::new("MM", "MMR", "Myanmar"),
+    Country::new("MN", "MNG", "Mongolia"),
+    Country::new("MO", "MAC", "Macao"),
+    Country::new("MP", "MNP", "Northern Mariana Islands"),
+    Country::new("MQ", "MTQ", "Martinique"),
+    Country::new("MR", "MRT", "Mauritania"),
+    Country::new("MS", "MSR", "Montserrat"),
+    Country::new("MT", "MLT", "Malta"),
+    Country::new("MU", "MUS", "Mauritius"),
+    Country::new("MV", "MDV", "Maldives"),
+    Country::new("MW", "MWI", "Malawi"),
+    Country::new("MX", "MEX", "Mexico"),
+    Country::new("MY", "MYS", "Malaysia"),
+    Country::new("MZ", "MOZ", "Mozambique"),
+    Country::new("NA", "NAM", "Namibia"),
+    Country::new("NC", "NCL", "New Caledonia"),
+    Country::new("NE", "NER", "Niger"),
+    Country::new("NF", "NFK", "Norfolk Island"),
+    Country::new("NG", "NGA", "Nigeria"),
+    Country::new("NI", "NIC", "Nicaragua"),
+    Country::new("NL", "NLD", "Netherlands"),
+    Country::new("NO", "NOR", "Norway"),
+    Country::new("NP", "NPL", "Nepal"),
+    Country::new("NR", "NRU", "Nauru"),
+    Country::new("NU", "NIU", "Niue"),
+    Country::new("NZ", "NZL", "New Zealand"),
+    Country::new("OM", "OMN", "Oman"),
+    Country::new("PA", "PAN", "Panama"),
+    Country::new("PE", "PER", "Peru"),
+    Country::new("PF", "PYF", "French Polynesia"),
+    Country::new("PG", "PNG", "Papua New Guinea"),
+    Country::new("PH", "PHL", "Philippines"),
+    Country::new("PK", "PAK", "Pakistan"),
+    Country::new("PL", "POL", "Poland"),
+    Country::new("PM", "SPM", "Saint Pierre and Miquelon"),
+    Country::new("PN", "PCN", "Pitcairn"),
+    Country::new("PR", "PRI", "Puerto Rico"),
+    Country::new("PS", "PSE", "Palestine"),
+    Country::new("PT", "PRT", "Portugal"),
+    Country::new("PW", "PLW", "Palau"),
+    Country::new("PY", "PRY", "Paraguay"),
+    Country::new("QA", "QAT", "Qatar"),
+    Country::new("RE", "REU", "Réunion"),
+    Country::new("RO", "ROU", "Romania"),
+    Country::new("RS", "SRB", "Serbia"),
+    Country::new("RU", "RUS", "Russia"),
+    Country::new("RW", "RWA", "Rwanda"),
+    Country::new("SA", "SAU", "Saudi Arabia"),
+    Country::new("SB", "SLB", "Solomon Islands"),
+    Country::new("SC", "SYC", "Seychelles"),
+    Country::new("SD", "SDN", "Sudan"),
+    Country::new("SE", "SWE", "Sweden"),
+    Country::new("SG", "SGP", "Singapore"),
+    Country::new("SH", "SHN", "Saint Helena"),
+    Country::new("SI", "SVN", "Slovenia"),
+    Country::new("SJ", "SJM", "Svalbard and Jan Mayen"),
+    Country::new("SK", "SVK", "Slovakia"),
+    Country::new("SL", "SLE", "Sierra Leone"),
+    Country::new("SM", "SMR", "San Marino"),
+    Country::new("SN", "SEN", "Senegal"),
+    Country::new("SO", "SOM", "Somalia"),
+    Country::new("SR", "SUR", "Suriname"),
+    Country::new("SS", "SSD", "South Sudan"),
+    Country::new("ST", "STP", "Sao Tome and Principe"),
+    Country::new("SV", "SLV", "El Salvador"),
+    Country::new("SX", "SXM", "Sint Maarten"),
+    Country::new("SY", "SYR", "Syria"),
+    Country::new("SZ", "SWZ", "Eswatini"),
+    Country::new("TC", "TCA", "Turks and Caicos Islands"),
+    Country::new("TD", "TCD", "Chad"),
+    Country::new("TF", "ATF", "French Southern Territories"),
+    Country::new("TG", "TGO", "Togo"),
+    Country::new("TH", "THA", "Thailand"),
+    Country::new("TJ", "TJK", "Tajikistan"),
+    Country::new("TK", "TKL", "Tokelau"),
+    Country::new("TL", "TLS", "Timor-Leste"),
+    Country::new("TM", "TKM", "Turkmenistan"),
+    Country::new("TN", "TUN", "Tunisia"),
+    Country::new("TO", "TON", "Tonga"),
+    Country::new("TR", "TUR", "Turkey"),
+    Country::new("TT", "TTO", "Trinidad and Tobago"),
+    Country::new("TV", "TUV", "Tuvalu"),
+    Country::new("TW", "TWN", "Taiwan"),
+    Country::new("TZ", "TZA", "Tanzania"),
+    Country::new("UA", "UKR", "Ukraine"),
+    Country::new("UG", "UGA", "Uganda"),
+    Country::new("UM", "UMI", "United States Minor Outlying Islands"),
+    Country::new("US", "USA", "United States"),
+    Country::new("UY", "URY", "Uruguay"),
+    Country::new("UZ", "UZB", "Uzbekistan"),
+    Country::new("VA", "VAT", "Vatican City"),
+    Country::new("VC", "VCT", "Saint Vincent and the Grenadines"),
+    Country::new("VE", "VEN", "Venezuela"),
+    Country::new("VG", "VGB", "British Virgin Islands"),
+    Country::new("VI", "VIR", "U.S. Virgin Islands"),
+    Country::new("VN", "VNM", "Vietnam"),
+    Country::new("VU", "VUT", "Vanuatu"),
+    Country::new("WF", "WLF", "Wallis and Futuna"),
+    Country::new("WS", "WSM", "Samoa"),
+    Country::new("YE", "YEM", "Yemen"),
+    Country::new("YT", "MYT", "Mayotte"),
+    Country::new("ZA", "ZAF", "South Africa"),
+    Country::new("ZM", "ZMB", "Zambia"),
+    Country::new("ZW", "ZWE", "Zimbabwe"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(input: &str) -> Option<&'static str> {
+        normalize_country(input).map(|c| c.alpha2)
+    }
+
+    #[test]
+    fn normalizes_codes_and_names() {
+        assert_eq!(code("kr"), Some("KR"));
+        assert_eq!(code("KOR"), Some("KR"));
+        assert_eq!(code("South Korea"), Some("KR"));
+        assert_eq!(code("  united   kingdom "), Some("GB"));
+        assert_eq!(code("Réunion"), Some("RE"));
+    }
+
+    #[test]
+    fn normalizes_common_spellings() {
+        assert_eq!(code("Korea"), Some("KR"));
+        assert_eq!(code("한국"), Some("KR"));
+        assert_eq!(code("U.S.A."), Some("US"));
+        assert_eq!(code("UK"), Some("GB"));
+        assert_eq!(code("日本"), Some("JP"));
+        assert_eq!(code("Holland"), Some("NL"));
+    }
+
+    #[test]
+    fn rejects_unknown_input() {
+        assert_eq!(code(""), None);
+        assert_eq!(code("   "), None);
+        assert_eq!(code("Atlantis"), None);
+    }
+
+    #[test]
+    fn names_fall_back_to_the_code() {
+        assert_eq!(country_name("JP"), "Japan");
+        assert_eq!(country_name("Atlantis"), "Atlantis");
+    }
+}
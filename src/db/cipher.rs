@@ -0,0 +1,145 @@
+use crate::db::operations::{checkpoint_database, remove_wal_files};
+use crate::error::Context;
+use crate::{GnedbyError, Result};
+use dialoguer::Password;
+use rusqlite::Connection;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Environment variable holding the database passphrase, for scripts and
+/// servers that cannot answer a prompt.
+pub const DB_KEY_ENV: &str = "GNEDBY_DB_KEY";
+
+/// Every plain SQLite file starts with this header. SQLCipher encrypts the
+/// whole file, header included.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Returns true when `path` holds an encrypted database. Missing and empty files
+/// are treated as plain, since SQLite creates them unencrypted.
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e).context(format!("Failed to open database file: {:?}", path)),
+    };
+
+    let mut header = [0u8; 16];
+    let read = file.read(&mut header)?;
+    Ok(read > 0 && header[..read] != SQLITE_HEADER[..read])
+}
+
+/// Returns the passphrase for the database at `path`, or `None` when it is not
+/// encrypted. The passphrase is read from `GNEDBY_DB_KEY` or asked for once per run.
+pub fn key_for(path: &Path) -> Result<Option<String>> {
+    if !is_encrypted(path)? {
+        return Ok(None);
+    }
+    ensure_supported()?;
+
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(Some(passphrase.clone()));
+    }
+    let passphrase = match env::var(DB_KEY_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => passphrase,
+        _ => Password::new()
+            .with_prompt("Database passphrase")
+            .interact()?,
+    };
+    Ok(Some(PASSPHRASE.get_or_init(|| passphrase).clone()))
+}
+
+/// Asks for a new passphrase (twice), unless `GNEDBY_DB_KEY` is set.
+pub fn new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = env::var(DB_KEY_ENV) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+
+    let passphrase = Password::new()
+        .with_prompt("New database passphrase")
+        .with_confirmation("Confirm passphrase", "Passphrases do not match")
+        .interact()?;
+    if passphrase.is_empty() {
+        return Err(GnedbyError::Validation(
+            "The passphrase must not be empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Unlocks `conn` with `key`. Must run before any other statement.
+pub fn apply_key(conn: &Connection, key: &str) -> Result<()> {
+    conn.pragma_update(None, "key", key)?;
+    // SQLCipher only checks the key once the first page is read.
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|_| {
+            GnedbyError::Auth("Wrong passphrase for the encrypted database".to_string())
+        })?;
+    Ok(())
+}
+
+/// Rewrites the plain database at `path` as a SQLCipher database keyed by `key`.
+pub fn encrypt_database(path: &Path, key: &str) -> Result<()> {
+    ensure_supported()?;
+    if is_encrypted(path)? {
+        return Err(GnedbyError::Conflict(
+            "The database is already encrypted".to_string(),
+        ));
+    }
+
+    checkpoint_database(path)?;
+    let conn = Connection::open(path)?;
+    export_database(conn, path, key)
+}
+
+/// Rewrites the encrypted database at `path` as a plain SQLite file.
+pub fn decrypt_database(path: &Path, key: &str) -> Result<()> {
+    ensure_supported()?;
+    if !is_encrypted(path)? {
+        return Err(GnedbyError::Conflict(
+            "The database is not encrypted".to_string(),
+        ));
+    }
+
+    checkpoint_database(path)?;
+    let conn = Connection::open(path)?;
+    apply_key(&conn, key)?;
+    export_database(conn, path, "")
+}
+
+/// Copies the database open on `conn` into a new file keyed by `key` (empty for
+/// plain text), then swaps it in place of `path`.
+fn export_database(conn: Connection, path: &Path, key: &str) -> Result<()> {
+    let export_path = path.with_extension("db.export");
+    if export_path.exists() {
+        fs::remove_file(&export_path)?;
+    }
+
+    conn.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        (export_path.to_string_lossy(), key),
+    )?;
+    conn.query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+        .context("Failed to export the database")?;
+    conn.execute("DETACH DATABASE export", [])?;
+    drop(conn);
+
+    fs::rename(&export_path, path).context("Failed to replace the database file")?;
+    remove_wal_files(path)?;
+    Ok(())
+}
+
+fn ensure_supported() -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(GnedbyError::Validation(
+            "This build of gnedby has no encryption support. Reinstall it with `cargo install gnedby --features sqlcipher`.".to_string(),
+        ));
+    }
+    Ok(())
+}
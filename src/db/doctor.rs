@@ -0,0 +1,422 @@
+use crate::backup::move_into_backups;
+use crate::db::models::{is_various_artists, ArtistCredit, ArtistRole, Format};
+use crate::db::operations::{
+    column_exists, insert_artist_credits, mark_edited, open_database_read_only,
+};
+use crate::db::romanize::search_key;
+use crate::{GnedbyError, Result};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Album columns read into `String` fields; a NULL in any of them makes loading
+/// the album fail.
+const TEXT_COLUMNS: [&str; 6] = [
+    "artist",
+    "album",
+    "genre",
+    "release_date",
+    "source_url",
+    "artwork_url",
+];
+
+/// Columns that must not be empty for an album to make sense.
+const REQUIRED_COLUMNS: [&str; 2] = ["artist", "album"];
+
+/// Tables whose rows belong to an album.
+const ALBUM_TABLES: [&str; 3] = ["album_artists", "tracks", "album_genres"];
+
+/// Files that sync and encryption leave next to the database when interrupted.
+/// They are only ever partial copies, so they are safe to delete.
+const LEFTOVER_EXTENSIONS: [&str; 2] = ["db.temp", "db.export"];
+
+/// A full copy of the database kept by older versions before risky operations.
+/// It may be the only copy of some data, so it is moved into the backups rather
+/// than deleted.
+const BACKUP_EXTENSION: &str = "db.bak";
+
+/// A problem found by `gnedby doctor`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// A message from `PRAGMA integrity_check`. The database is damaged and can
+    /// only be fixed by restoring a backup.
+    Integrity(String),
+    InvalidFormat {
+        id: i64,
+        value: Option<String>,
+    },
+    InvalidRole {
+        album_id: i64,
+        position: i64,
+        value: Option<String>,
+    },
+    MissingField {
+        id: i64,
+        column: &'static str,
+    },
+    InvalidDate {
+        id: i64,
+        value: String,
+    },
+    OrphanedRows {
+        table: &'static str,
+        count: i64,
+    },
+    LeftoverFile(PathBuf),
+    LeftoverBackup(PathBuf),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Integrity(message) => write!(f, "Integrity check: {}", message),
+            Issue::InvalidFormat { id, value } => {
+                write!(f, "Album {} has an invalid format: {:?}", id, value)
+            }
+            Issue::InvalidRole {
+                album_id,
+                position,
+                value,
+            } => write!(
+                f,
+                "Album {} has an invalid role for artist #{}: {:?}",
+                album_id,
+                position + 1,
+                value
+            ),
+            Issue::MissingField { id, column } => write!(f, "Album {} has no {}", id, column),
+            Issue::InvalidDate { id, value } => {
+                write!(f, "Album {} has an invalid release date: {:?}", id, value)
+            }
+            Issue::OrphanedRows { table, count } => {
+                write!(f, "{} row(s) in {} belong to deleted albums", count, table)
+            }
+            Issue::LeftoverFile(path) => write!(f, "Leftover file: {}", path.display()),
+            Issue::LeftoverBackup(path) => write!(
+                f,
+                "Database copy outside the backups directory: {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// A change that resolves an `Issue`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Repair {
+    SetFormat {
+        id: i64,
+        format: Format,
+    },
+    SetRole {
+        album_id: i64,
+        position: i64,
+        role: ArtistRole,
+    },
+    SetText {
+        id: i64,
+        column: &'static str,
+        value: String,
+    },
+    DeleteOrphans {
+        table: &'static str,
+    },
+    RemoveFile(PathBuf),
+    MoveToBackups(PathBuf),
+}
+
+impl Issue {
+    /// The repair `gnedby doctor --fix` applies, or `None` when the issue needs
+    /// a decision only the user can make, or a restore from a backup.
+    pub fn automatic_repair(&self) -> Option<Repair> {
+        match self {
+            Issue::Integrity(_) => None,
+            Issue::InvalidFormat { id, value } => Some(Repair::SetFormat {
+                id: *id,
+                format: value
+                    .as_deref()
+                    .and_then(|v| Format::parse(v.trim()))
+                    .unwrap_or(Format::Cd),
+            }),
+            Issue::InvalidRole {
+                album_id,
+                position,
+                value,
+            } => Some(Repair::SetRole {
+                album_id: *album_id,
+                position: *position,
+                role: value
+                    .as_deref()
+                    .and_then(|v| ArtistRole::parse(v.trim()))
+                    .unwrap_or(ArtistRole::Primary),
+            }),
+            Issue::MissingField { id, column } => Some(Repair::SetText {
+                id: *id,
+                column,
+                value: match *column {
+                    "artist" => "Unknown Artist".to_string(),
+                    "album" => "Untitled".to_string(),
+                    _ => String::new(),
+                },
+            }),
+            Issue::InvalidDate { id, value } => {
+                normalize_release_date(value).map(|date| Repair::SetText {
+                    id: *id,
+                    column: "release_date",
+                    value: date,
+                })
+            }
+            Issue::OrphanedRows { table, .. } => Some(Repair::DeleteOrphans { table }),
+            Issue::LeftoverFile(path) => Some(Repair::RemoveFile(path.clone())),
+            Issue::LeftoverBackup(path) => Some(Repair::MoveToBackups(path.clone())),
+        }
+    }
+}
+
+impl Repair {
+    /// Whether the repair changes the database, rather than files next to it.
+    pub fn needs_database(&self) -> bool {
+        !matches!(self, Repair::RemoveFile(_) | Repair::MoveToBackups(_))
+    }
+}
+
+/// Rewrites common date spellings ("2020", "2020.3.1", "2020/03/01", "20200301")
+/// as `YYYY-MM-DD`, filling a missing month or day with 01.
+pub fn normalize_release_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    let parts: Vec<&str> = if value.len() == 8 && value.chars().all(|c| c.is_ascii_digit()) {
+        vec![&value[0..4], &value[4..6], &value[6..8]]
+    } else {
+        value
+            .split(['-', '.', '/', ' '])
+            .filter(|part| !part.is_empty())
+            .collect()
+    };
+
+    if parts.is_empty() || parts.len() > 3 || parts[0].len() != 4 {
+        return None;
+    }
+    let numbers: Vec<u32> = parts
+        .iter()
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let date = NaiveDate::from_ymd_opt(
+        numbers[0] as i32,
+        numbers.get(1).copied().unwrap_or(1),
+        numbers.get(2).copied().unwrap_or(1),
+    )?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+/// Runs every check against the database file at `path`, opened read-only and
+/// without migrating it, so a database that fails to open normally can still
+/// be diagnosed.
+pub fn check_database(path: &Path) -> Result<Vec<Issue>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    find_issues(&open_database_read_only(path)?)
+}
+
+/// Runs every database check. Checks on tables or columns that an older,
+/// unmigrated database does not have yet are skipped.
+pub fn find_issues(conn: &Connection) -> Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    issues.extend(
+        messages
+            .into_iter()
+            .filter(|message| message != "ok")
+            .map(Issue::Integrity),
+    );
+
+    if column_exists(conn, "albums", "format")? {
+        let mut stmt = conn.prepare("SELECT id, CAST(format AS TEXT) FROM albums ORDER BY id")?;
+        let formats = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (id, value) in formats {
+            if value.as_deref().and_then(Format::parse).is_none() {
+                issues.push(Issue::InvalidFormat { id, value });
+            }
+        }
+    }
+
+    if column_exists(conn, "album_artists", "role")? {
+        let mut stmt = conn.prepare(
+            "SELECT album_id, position, CAST(role AS TEXT) FROM album_artists ORDER BY album_id",
+        )?;
+        let roles = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (album_id, position, value) in roles {
+            if value.as_deref().and_then(ArtistRole::parse).is_none() {
+                issues.push(Issue::InvalidRole {
+                    album_id,
+                    position,
+                    value,
+                });
+            }
+        }
+    }
+
+    for column in TEXT_COLUMNS {
+        if !column_exists(conn, "albums", column)? {
+            continue;
+        }
+        let condition = if REQUIRED_COLUMNS.contains(&column) {
+            format!("{0} IS NULL OR trim({0}) = ''", column)
+        } else {
+            format!("{} IS NULL", column)
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id FROM albums WHERE {} ORDER BY id",
+            condition
+        ))?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        issues.extend(ids.into_iter().map(|id| Issue::MissingField { id, column }));
+    }
+
+    // Dates must start with a real YYYY-MM-DD date; a bare "2020" would be read
+    // by SQLite as a Julian day number, and date() rolls "02-30" over to March.
+    let mut stmt = conn.prepare(
+        "SELECT id, release_date FROM albums
+         WHERE release_date != ''
+           AND (release_date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]*'
+                OR date(substr(release_date, 1, 10)) IS NOT substr(release_date, 1, 10))
+         ORDER BY id",
+    )?;
+    let dates = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    issues.extend(
+        dates
+            .into_iter()
+            .map(|(id, value)| Issue::InvalidDate { id, value }),
+    );
+
+    for table in ALBUM_TABLES {
+        if !column_exists(conn, table, "album_id")? {
+            continue;
+        }
+        let count: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE album_id NOT IN (SELECT id FROM albums)",
+                table
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        if count > 0 {
+            issues.push(Issue::OrphanedRows { table, count });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Finds temporary files and stray database copies left next to the database
+/// at `db_path`.
+pub fn find_leftover_files(db_path: &Path) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = LEFTOVER_EXTENSIONS
+        .iter()
+        .map(|extension| db_path.with_extension(extension))
+        .filter(|path| path.exists())
+        .map(Issue::LeftoverFile)
+        .collect();
+    let backup = db_path.with_extension(BACKUP_EXTENSION);
+    if backup.exists() {
+        issues.push(Issue::LeftoverBackup(backup));
+    }
+    issues
+}
+
+/// Applies a repair to the database, or to the file system for leftover files.
+/// `Database::apply_repair` runs it in a transaction.
+pub fn apply_repair(conn: &Connection, repair: &Repair) -> Result<()> {
+    match repair {
+        Repair::SetFormat { id, format } => {
+            conn.execute(
+                "UPDATE albums SET format = ?1 WHERE id = ?2",
+                (format.as_str(), id),
+            )?;
+        }
+        Repair::SetRole {
+            album_id,
+            position,
+            role,
+        } => {
+            conn.execute(
+                "UPDATE album_artists SET role = ?1 WHERE album_id = ?2 AND position = ?3",
+                (role.as_str(), album_id, position),
+            )?;
+        }
+        Repair::SetText { id, column, value } => {
+            conn.execute(
+                &format!("UPDATE albums SET {} = ?1 WHERE id = ?2", column),
+                (value, id),
+            )?;
+            if matches!(*column, "artist" | "album") {
+                conn.execute(
+                    &format!("UPDATE albums SET {}_key = ?1 WHERE id = ?2", column),
+                    (search_key(value), id),
+                )?;
+            }
+            if *column == "artist" {
+                conn.execute("DELETE FROM album_artists WHERE album_id = ?", [id])?;
+                insert_artist_credits(conn, *id, &ArtistCredit::parse(value))?;
+                conn.execute(
+                    "UPDATE albums SET compilation = ?1 WHERE id = ?2",
+                    (is_various_artists(value), id),
+                )?;
+            }
+            mark_edited(conn, *id, column)?;
+        }
+        Repair::DeleteOrphans { table } => {
+            conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE album_id NOT IN (SELECT id FROM albums)",
+                    table
+                ),
+                [],
+            )?;
+        }
+        Repair::RemoveFile(_) | Repair::MoveToBackups(_) => apply_file_repair(repair)?,
+    }
+    Ok(())
+}
+
+/// Applies a repair to the files next to the database, which needs no
+/// connection. See [`Repair::needs_database`].
+pub fn apply_file_repair(repair: &Repair) -> Result<()> {
+    match repair {
+        Repair::RemoveFile(path) => fs::remove_file(path)?,
+        Repair::MoveToBackups(path) => {
+            move_into_backups(path, "bak")?;
+        }
+        _ => {
+            return Err(GnedbyError::Validation(
+                "This repair changes the database".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
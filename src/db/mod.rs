@@ -1,5 +1,9 @@
+pub mod cipher;
+pub mod doctor;
 pub mod models;
 pub mod operations;
+pub mod romanize;
+pub mod text;
 
 pub use models::*;
 pub use operations::*;
@@ -1,3 +1,4 @@
+use crate::db::text::fold;
 use rusqlite::types::{FromSql, FromSqlError, ValueRef};
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -14,7 +15,7 @@ impl FromSql for Format {
     fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
         value
             .as_str()
-            .and_then(|s| Format::from_str(s).ok_or(FromSqlError::InvalidType))
+            .and_then(|s| Format::parse(s).ok_or(FromSqlError::InvalidType))
     }
 }
 
@@ -34,7 +35,7 @@ impl Format {
         }
     }
 
-    pub fn from_str(s: &str) -> Option<Self> {
+    pub fn parse(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "cd" => Some(Format::Cd),
             "lp" => Some(Format::Lp),
@@ -45,6 +46,174 @@ impl Format {
     }
 }
 
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtistRole {
+    Primary,
+    Featuring,
+    Various,
+}
+
+impl FromSql for ArtistRole {
+    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value
+            .as_str()
+            .and_then(|s| ArtistRole::parse(s).ok_or(FromSqlError::InvalidType))
+    }
+}
+
+impl ArtistRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtistRole::Primary => "primary",
+            ArtistRole::Featuring => "featuring",
+            ArtistRole::Various => "various",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "primary" => Some(ArtistRole::Primary),
+            "featuring" => Some(ArtistRole::Featuring),
+            "various" => Some(ArtistRole::Various),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ArtistCredit {
+    pub name: String,
+    pub role: ArtistRole,
+}
+
+impl ArtistCredit {
+    pub fn new(name: &str, role: ArtistRole) -> Self {
+        ArtistCredit {
+            name: name.trim().to_string(),
+            role,
+        }
+    }
+
+    /// Splits a display artist string such as "IU with SUGA" or "Zion.T (feat. Crush)"
+    /// into individual credits. Only explicit collaboration markers split names, so
+    /// band names like "Simon & Garfunkel" or "Earth, Wind & Fire" stay whole.
+    /// "Various Artists" becomes a single `Various` credit.
+    pub fn parse(artist: &str) -> Vec<ArtistCredit> {
+        let artist = artist.trim();
+        if is_various_artists(artist) {
+            return vec![ArtistCredit::new(VARIOUS_ARTISTS, ArtistRole::Various)];
+        }
+
+        let (main, featuring) = split_featuring(artist);
+
+        let mut credits: Vec<ArtistCredit> = split_names(main)
+            .into_iter()
+            .map(|name| ArtistCredit::new(name, ArtistRole::Primary))
+            .collect();
+        if let Some(featuring) = featuring {
+            credits.extend(
+                split_names(featuring)
+                    .into_iter()
+                    .map(|name| ArtistCredit::new(name, ArtistRole::Featuring)),
+            );
+        }
+
+        if credits.is_empty() {
+            credits.push(ArtistCredit::new(artist, ArtistRole::Primary));
+        }
+        credits
+    }
+
+    /// Credit names as the first version of [`ArtistCredit::parse`] split them,
+    /// on every `,` and `&`. Used to find credits that need rebuilding.
+    pub(crate) fn legacy_names(artist: &str) -> Vec<String> {
+        let artist = artist.trim();
+        if is_various_artists(artist) {
+            return vec![VARIOUS_ARTISTS.to_string()];
+        }
+        let (main, featuring) = split_featuring(artist);
+        let names: Vec<String> = [Some(main), featuring]
+            .into_iter()
+            .flatten()
+            .flat_map(|names| names.split([',', '&']))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if names.is_empty() {
+            vec![artist.to_string()]
+        } else {
+            names
+        }
+    }
+}
+
+pub fn is_various_artists(artist: &str) -> bool {
+    matches!(
+        artist.trim().to_lowercase().as_str(),
+        "various artists" | "various" | "v.a." | "va"
+    )
+}
+
+fn split_featuring(artist: &str) -> (&str, Option<&str>) {
+    const MARKERS: [&str; 7] = [
+        " (feat. ",
+        " (ft. ",
+        " (featuring ",
+        " [feat. ",
+        " feat. ",
+        " ft. ",
+        " featuring ",
+    ];
+
+    let lower = artist.to_lowercase();
+    for marker in MARKERS {
+        if let Some(idx) = lower.find(marker) {
+            // Markers are ASCII, so byte offsets in the lowercased string line up
+            // with the original as long as the prefix has the same length.
+            if lower.len() != artist.len() {
+                break;
+            }
+            let main = &artist[..idx];
+            let rest = artist[idx + marker.len()..].trim_end_matches([')', ']']);
+            return (main, Some(rest));
+        }
+    }
+    (artist, None)
+}
+
+/// Splits names joined by a collaboration marker, e.g. "IU with SUGA" or
+/// "Peggy Gou x Lenny Kravitz". `,` and `&` are left alone since they are
+/// common inside band names.
+fn split_names(names: &str) -> Vec<&str> {
+    const MARKERS: [&str; 3] = [" with ", " x ", " × "];
+
+    // ASCII lowercasing keeps byte offsets in step with `names`.
+    let lower = names.to_ascii_lowercase();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index < lower.len() {
+        match MARKERS
+            .iter()
+            .find(|marker| lower[index..].starts_with(*marker))
+        {
+            Some(marker) => {
+                parts.push(names[start..index].trim());
+                index += marker.len();
+                start = index;
+            }
+            None => index += lower[index..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    parts.push(names[start..].trim());
+    parts.retain(|name| !name.is_empty());
+    parts
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Album {
     pub id: Option<i64>,
@@ -54,6 +223,335 @@ pub struct Album {
     pub release_date: String,
     pub format: Format,
     pub source_url: String,
-    pub country: String,
+    /// Apple Music storefront the metadata was fetched from (ISO alpha-2).
+    pub storefront: Option<String>,
+    /// Country the artist or release comes from (ISO alpha-2).
+    pub origin_country: Option<String>,
     pub artwork_url: String,
+    /// Metadata provider the album was added from, e.g. "apple".
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// The album's id at `provider`.
+    #[serde(default)]
+    pub source_id: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub catalog_number: Option<String>,
+    /// UPC or EAN printed on the release.
+    #[serde(default)]
+    pub barcode: Option<String>,
+    /// Format description such as "Vinyl, LP, Album, Limited Edition".
+    #[serde(default)]
+    pub format_details: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<ArtistCredit>,
+    #[serde(default)]
+    pub compilation: bool,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    /// Fields changed by hand, which `gnedby refresh` leaves alone.
+    #[serde(default)]
+    pub edited_fields: Vec<String>,
+}
+
+/// A field whose stored value differs from the provider's, found by
+/// [`Album::changes_from`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// Fields `gnedby refresh` compares with the provider. The format is left
+/// out because it describes the copy in the collection, not the release.
+pub const REFRESH_FIELDS: &[&str] = &[
+    "artist",
+    "album",
+    "genre",
+    "release_date",
+    "source_url",
+    "storefront",
+    "origin_country",
+    "artwork_url",
+    "label",
+    "catalog_number",
+    "barcode",
+    "format_details",
+];
+
+impl Album {
+    /// The value of one of [`REFRESH_FIELDS`], empty when unset.
+    pub fn field(&self, field: &str) -> Option<&str> {
+        let value = match field {
+            "artist" => &self.artist,
+            "album" => &self.album,
+            "genre" => &self.genre,
+            "release_date" => &self.release_date,
+            "source_url" => &self.source_url,
+            "artwork_url" => &self.artwork_url,
+            "storefront" => return Some(self.storefront.as_deref().unwrap_or_default()),
+            "origin_country" => return Some(self.origin_country.as_deref().unwrap_or_default()),
+            "label" => return Some(self.label.as_deref().unwrap_or_default()),
+            "catalog_number" => return Some(self.catalog_number.as_deref().unwrap_or_default()),
+            "barcode" => return Some(self.barcode.as_deref().unwrap_or_default()),
+            "format_details" => return Some(self.format_details.as_deref().unwrap_or_default()),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Sets one of [`REFRESH_FIELDS`]. Changing the artist or genre also
+    /// resets the parsed artist credits or genre list to match.
+    pub fn set_field(&mut self, field: &str, value: String) {
+        let optional = || Some(value.clone()).filter(|value| !value.is_empty());
+        match field {
+            "artist" => {
+                self.artists = ArtistCredit::parse(&value);
+                self.compilation = is_various_artists(&value);
+                self.artist = value;
+            }
+            "album" => self.album = value,
+            "genre" => {
+                self.genres = Vec::new();
+                self.genre = value;
+            }
+            "release_date" => self.release_date = value,
+            "source_url" => self.source_url = value,
+            "artwork_url" => self.artwork_url = value,
+            "storefront" => self.storefront = optional(),
+            "origin_country" => self.origin_country = optional(),
+            "label" => self.label = optional(),
+            "catalog_number" => self.catalog_number = optional(),
+            "barcode" => self.barcode = optional(),
+            "format_details" => self.format_details = optional(),
+            _ => {}
+        }
+    }
+
+    /// Compares the stored album with `fresh` from its provider. Fields the
+    /// provider left empty and fields in `edited_fields` are skipped.
+    pub fn changes_from(&self, fresh: &Album) -> Vec<FieldChange> {
+        REFRESH_FIELDS
+            .iter()
+            .filter(|field| !self.edited_fields.iter().any(|edited| edited == *field))
+            .filter_map(|field| {
+                let old = self.field(field)?;
+                let new = fresh.field(field)?;
+                (!new.is_empty() && new != old).then(|| FieldChange {
+                    field,
+                    old: old.to_string(),
+                    new: new.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Filters for `Database::list_albums`. Text filters match regardless of case,
+/// accents, width and script (romanized Hangul and kana).
+#[derive(Debug, Clone, Default)]
+pub struct AlbumFilter {
+    pub year: Option<i32>,
+    pub artist: Option<String>,
+    pub genre: Option<String>,
+    pub format: Option<Format>,
+    pub country: Option<String>,
+    /// Matches either the artist or the album title.
+    pub query: Option<String>,
+}
+
+/// Window of results for `Database::list_albums`. `after` continues from the
+/// album with that ID in the current sort order (keyset pagination), which stays
+/// fast on large collections, while `offset` skips a number of rows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Page {
+    pub limit: Option<u32>,
+    pub offset: u32,
+    pub after: Option<i64>,
+}
+
+/// Dimension that `Database::get_stats` groups album counts by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsGroup {
+    Artist,
+    Year,
+    /// With `top_level`, sub-genres are rolled up into their top-level ancestor.
+    Genre {
+        top_level: bool,
+    },
+    Format,
+    Country,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Track {
+    pub disc_number: i64,
+    pub track_number: i64,
+    pub title: String,
+    pub duration_ms: Option<i64>,
+}
+
+impl Track {
+    /// Formats the duration as `m:ss`, or an empty string when unknown.
+    pub fn duration_display(&self) -> String {
+        match self.duration_ms {
+            Some(ms) => {
+                let seconds = ms / 1000;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Genre {
+    pub id: i64,
+    pub name: String,
+    pub parent: Option<String>,
+    pub album_count: i64,
+}
+
+/// Genres created on first run so that common Apple Music genres roll up sensibly.
+pub const DEFAULT_GENRE_HIERARCHY: &[(&str, &[&str])] = &[
+    (
+        "Pop",
+        &["K-Pop", "J-Pop", "Indie Pop", "Dance", "Singer/Songwriter"],
+    ),
+    (
+        "Rock",
+        &["Alternative", "Indie Rock", "Hard Rock", "Punk", "Metal"],
+    ),
+    ("Hip-Hop/Rap", &["K-Hip-Hop", "Rap"]),
+    ("R&B/Soul", &["K-R&B", "Soul"]),
+    ("Electronic", &["House", "Techno", "Ambient"]),
+    ("Jazz", &[]),
+    ("Classical", &[]),
+    ("Soundtrack", &["Original Score"]),
+    ("Folk", &[]),
+    ("Blues", &[]),
+    ("Country", &[]),
+];
+
+/// Normalizes a genre name so that "K-Pop", "K Pop" and "kpop" compare equal.
+pub fn genre_key(name: &str) -> String {
+    fold(name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits a free-text genre field such as "K-Pop, Ballad" into individual genres.
+/// Slashes are kept because Apple uses them in names like "Hip-Hop/Rap".
+pub fn split_genres(genre: &str) -> Vec<String> {
+    genre
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn album() -> Album {
+        Album {
+            id: Some(1),
+            artist: "IU".to_string(),
+            album: "Palette".to_string(),
+            genre: "K-Pop".to_string(),
+            release_date: "2017-04-21".to_string(),
+            format: Format::Cd,
+            source_url: "https://music.apple.com/kr/album/palette/1".to_string(),
+            storefront: Some("KR".to_string()),
+            origin_country: Some("KR".to_string()),
+            artwork_url: "https://example.com/600x600bb.jpg".to_string(),
+            provider: Some("apple".to_string()),
+            source_id: Some("1".to_string()),
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format_details: None,
+            artists: Vec::new(),
+            compilation: false,
+            genres: Vec::new(),
+            edited_fields: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn changes_skip_edited_and_empty_fields() {
+        let mut stored = album();
+        stored.edited_fields = vec!["genre".to_string()];
+        let mut fresh = album();
+        fresh.album = "Palette (Deluxe)".to_string();
+        fresh.genre = "Pop".to_string();
+        fresh.origin_country = None;
+        fresh.label = Some("LOEN".to_string());
+
+        let changes = stored.changes_from(&fresh);
+        let fields: Vec<&str> = changes.iter().map(|change| change.field).collect();
+        assert_eq!(fields, ["album", "label"]);
+        assert_eq!(changes[1].old, "");
+
+        for change in changes {
+            stored.set_field(change.field, change.new);
+        }
+        assert_eq!(stored.album, "Palette (Deluxe)");
+        assert_eq!(stored.label.as_deref(), Some("LOEN"));
+        assert_eq!(stored.genre, "K-Pop");
+    }
+
+    fn names(artist: &str) -> Vec<(String, ArtistRole)> {
+        ArtistCredit::parse(artist)
+            .into_iter()
+            .map(|credit| (credit.name, credit.role))
+            .collect()
+    }
+
+    #[test]
+    fn parse_keeps_band_names_whole() {
+        assert_eq!(
+            names("Simon & Garfunkel"),
+            [("Simon & Garfunkel".to_string(), ArtistRole::Primary)]
+        );
+        assert_eq!(
+            names("Earth, Wind & Fire"),
+            [("Earth, Wind & Fire".to_string(), ArtistRole::Primary)]
+        );
+    }
+
+    #[test]
+    fn parse_splits_collaboration_markers() {
+        assert_eq!(
+            names("IU with SUGA"),
+            [
+                ("IU".to_string(), ArtistRole::Primary),
+                ("SUGA".to_string(), ArtistRole::Primary),
+            ]
+        );
+        assert_eq!(
+            names("Peggy Gou X Lenny Kravitz"),
+            [
+                ("Peggy Gou".to_string(), ArtistRole::Primary),
+                ("Lenny Kravitz".to_string(), ArtistRole::Primary),
+            ]
+        );
+        assert_eq!(
+            names("Zion.T (feat. Earth, Wind & Fire)"),
+            [
+                ("Zion.T".to_string(), ArtistRole::Primary),
+                ("Earth, Wind & Fire".to_string(), ArtistRole::Featuring),
+            ]
+        );
+        assert_eq!(
+            names("Various Artists"),
+            [(VARIOUS_ARTISTS.to_string(), ArtistRole::Various)]
+        );
+    }
 }
@@ -1,33 +1,117 @@
-use crate::db::models::Album;
-use anyhow::{Context, Result};
+use crate::config::load_metadata_config;
+use crate::country::{country_name, normalize_country};
+use crate::db::cipher::{apply_key, key_for};
+use crate::db::doctor::{apply_repair, find_issues, Issue, Repair};
+use crate::db::models::{
+    genre_key, split_genres, Album, AlbumFilter, ArtistCredit, ArtistRole, Genre, Page, StatsGroup,
+    Track, DEFAULT_GENRE_HIERARCHY,
+};
+use crate::db::romanize::{search_key, search_pattern, ROMANIZED_GLOB};
+use crate::db::text::{fold, register_text_functions};
+use crate::error::Context;
+use crate::metadata::{apple_album_id_from_url, apple_artwork_url, DEFAULT_ARTWORK_SIZE};
+use crate::{GnedbyError, Result};
 use directories::ProjectDirs;
-use rusqlite::{Connection, OptionalExtension};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row, TransactionBehavior};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
 
+/// Number of SQLite connections that may run queries at the same time.
+const POOL_SIZE: usize = 4;
+
+/// How long a connection waits on a lock held by another connection or process
+/// before giving up with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handle to the album database. Queries run on Tokio's blocking thread pool
+/// using a small pool of WAL-mode connections, so a slow query never stalls the
+/// async runtime and readers do not wait on each other.
+#[derive(Clone)]
 pub struct Database {
-    conn: Mutex<Connection>,
+    pool: Arc<ConnectionPool>,
+}
+
+struct ConnectionPool {
+    path: PathBuf,
+    /// SQLCipher passphrase when the database is encrypted.
+    key: Option<String>,
+    idle: Mutex<Vec<Connection>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ConnectionPool {
+    fn checkout(&self) -> Result<Connection> {
+        let idle = self.idle.lock().unwrap().pop();
+        match idle {
+            Some(conn) => Ok(conn),
+            None => open_connection(&self.path, self.key.as_deref()),
+        }
+    }
+
+    fn checkin(&self, conn: Connection) {
+        self.idle.lock().unwrap().push(conn);
+    }
 }
 
 impl Database {
     pub async fn new() -> Result<Self> {
         let db_path = get_db_path()?;
-        let conn = Connection::open(&db_path)?;
+        Self::with_path(&db_path).await
+    }
+
+    pub async fn with_path(path: &Path) -> Result<Self> {
         let db = Database {
-            conn: Mutex::new(conn),
+            pool: Arc::new(ConnectionPool {
+                path: path.to_path_buf(),
+                key: key_for(path)?,
+                idle: Mutex::new(Vec::new()),
+                permits: Arc::new(Semaphore::new(POOL_SIZE)),
+            }),
         };
         db.init().await?;
         Ok(db)
     }
 
+    /// Runs `f` with a pooled connection on a blocking thread.
+    async fn call<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .pool
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .context("Database connection pool closed")?;
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let mut conn = pool.checkout()?;
+            let result = f(&mut conn);
+            pool.checkin(conn);
+            result
+        })
+        .await
+        .context("Database task panicked")?
+    }
+
     pub async fn init(&self) -> Result<()> {
-        let conn = self.conn.lock().await;
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS albums (
+        self.call(move |conn| {
+            // Migrations run in one immediate transaction so that two processes
+            // opening the database at once do not both try to apply them.
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let conn: &Connection = &tx;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS albums (
                 id INTEGER PRIMARY KEY,
                 artist TEXT NOT NULL,
                 album TEXT NOT NULL,
@@ -35,311 +119,1468 @@ impl Database {
                 release_date TEXT,
                 format TEXT,
                 source_url TEXT,
-                country TEXT,
+                storefront TEXT,
+                origin_country TEXT,
                 artwork_url TEXT
             )",
-            [],
-        )?;
-        Ok(())
+                [],
+            )?;
+            add_column_if_missing(conn, "albums", "storefront", "TEXT")?;
+            add_column_if_missing(conn, "albums", "origin_country", "TEXT")?;
+            migrate_legacy_country(conn)?;
+            add_column_if_missing(conn, "albums", "compilation", "INTEGER NOT NULL DEFAULT 0")?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS album_artists (
+                album_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                role TEXT NOT NULL,
+                PRIMARY KEY (album_id, position)
+            )",
+                [],
+            )?;
+            add_column_if_missing(conn, "albums", "artist_key", "TEXT")?;
+            add_column_if_missing(conn, "albums", "album_key", "TEXT")?;
+            add_column_if_missing(conn, "album_artists", "name_key", "TEXT")?;
+            backfill_artist_credits(conn)?;
+            rebuild_split_artist_credits(conn)?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS tracks (
+                album_id INTEGER NOT NULL,
+                disc_number INTEGER NOT NULL,
+                track_number INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                duration_ms INTEGER,
+                PRIMARY KEY (album_id, disc_number, track_number)
+            )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS genres (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                parent_id INTEGER
+            )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS genre_aliases (
+                alias TEXT PRIMARY KEY,
+                genre_id INTEGER NOT NULL
+            )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS album_genres (
+                album_id INTEGER NOT NULL,
+                genre_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (album_id, genre_id)
+            )",
+                [],
+            )?;
+            add_column_if_missing(conn, "tracks", "title_key", "TEXT")?;
+            add_column_if_missing(conn, "albums", "provider", "TEXT")?;
+            add_column_if_missing(conn, "albums", "source_id", "TEXT")?;
+            backfill_album_sources(conn)?;
+            add_column_if_missing(conn, "albums", "label", "TEXT")?;
+            add_column_if_missing(conn, "albums", "catalog_number", "TEXT")?;
+            add_column_if_missing(conn, "albums", "barcode", "TEXT")?;
+            add_column_if_missing(conn, "albums", "format_details", "TEXT")?;
+            add_column_if_missing(conn, "albums", "edited_fields", "TEXT")?;
+            upgrade_artwork_thumbnails(conn)?;
+            add_column_if_missing(
+                conn,
+                "albums",
+                "release_year",
+                "INTEGER GENERATED ALWAYS AS (CAST(strftime('%Y', release_date) AS INTEGER)) VIRTUAL",
+            )?;
+            create_indexes(conn)?;
+            seed_genres(conn)?;
+            backfill_album_genres(conn)?;
+            clear_latin_search_keys(conn)?;
+            rebuild_search_keys(conn)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
     }
 
     pub async fn add_album(&self, album: &Album) -> Result<i64> {
-        let conn = self.conn.lock().await;
-        conn.execute(
-            "INSERT INTO albums (artist, album, genre, release_date, format, source_url, country, artwork_url)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            (
-                &album.artist,
-                &album.album,
-                &album.genre,
-                &album.release_date,
-                &album.format.as_str(),
-                &album.source_url,
-                &album.country,
-                &album.artwork_url,
-            ),
-        )?;
-        let id = conn.last_insert_rowid();
+        let album = album.clone();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO albums (artist, album, genre, release_date, format, source_url, storefront, origin_country, artwork_url, compilation, artist_key, album_key, provider, source_id, label, catalog_number, barcode, format_details, edited_fields)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+                params![
+                    &album.artist,
+                    &album.album,
+                    &album.genre,
+                    &album.release_date,
+                    &album.format.as_str(),
+                    &album.source_url,
+                    &album.storefront,
+                    &album.origin_country,
+                    &album.artwork_url,
+                    album.compilation,
+                    search_key(&album.artist),
+                    search_key(&album.album),
+                    &album.provider,
+                    &album.source_id,
+                    &album.label,
+                    &album.catalog_number,
+                    &album.barcode,
+                    &album.format_details,
+                    edited_fields_value(&album.edited_fields),
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+
+            let credits = if album.artists.is_empty() {
+                ArtistCredit::parse(&album.artist)
+            } else {
+                album.artists.clone()
+            };
+            insert_artist_credits(&tx, id, &credits)?;
+
+            let genres = if album.genres.is_empty() {
+                split_genres(&album.genre)
+            } else {
+                album.genres.clone()
+            };
+            set_album_genres(&tx, id, &genres)?;
+            tx.commit()?;
+
+            Ok(id)
+        })
+        .await
+    }
+
+    /// Saves changes to a stored album, such as those from `gnedby refresh`.
+    /// Artist credits and genres are rebuilt from the artist and genre text
+    /// when `artists` or `genres` is empty.
+    pub async fn update_album(&self, album: &Album) -> Result<()> {
+        let album = album.clone();
+        let id = album
+            .id
+            .ok_or_else(|| GnedbyError::Validation("Album has no ID".to_string()))?;
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let rows_affected = tx.execute(
+                "UPDATE albums SET artist = ?1, album = ?2, genre = ?3, release_date = ?4, format = ?5, source_url = ?6, storefront = ?7, origin_country = ?8, artwork_url = ?9, compilation = ?10, artist_key = ?11, album_key = ?12, provider = ?13, source_id = ?14, label = ?15, catalog_number = ?16, barcode = ?17, format_details = ?18, edited_fields = ?19
+                 WHERE id = ?20",
+                params![
+                    &album.artist,
+                    &album.album,
+                    &album.genre,
+                    &album.release_date,
+                    &album.format.as_str(),
+                    &album.source_url,
+                    &album.storefront,
+                    &album.origin_country,
+                    &album.artwork_url,
+                    album.compilation,
+                    search_key(&album.artist),
+                    search_key(&album.album),
+                    &album.provider,
+                    &album.source_id,
+                    &album.label,
+                    &album.catalog_number,
+                    &album.barcode,
+                    &album.format_details,
+                    edited_fields_value(&album.edited_fields),
+                    id,
+                ],
+            )?;
+
+            if rows_affected == 0 {
+                return Err(GnedbyError::NotFound(format!(
+                    "Album with ID {} not found",
+                    id
+                )));
+            }
 
-        Ok(id)
+            let credits = if album.artists.is_empty() {
+                ArtistCredit::parse(&album.artist)
+            } else {
+                album.artists.clone()
+            };
+            tx.execute("DELETE FROM album_artists WHERE album_id = ?", [id])?;
+            insert_artist_credits(&tx, id, &credits)?;
+
+            let genres = if album.genres.is_empty() {
+                split_genres(&album.genre)
+            } else {
+                album.genres.clone()
+            };
+            set_album_genres(&tx, id, &genres)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
     }
 
+    /// Lists albums matching `filter`, sorted by `order_by` (id, album, artist or
+    /// year) and limited to `page`.
     pub async fn list_albums(
         &self,
-        year: Option<i32>,
-        artist: Option<&str>,
-        genre: Option<&str>,
-        format: Option<&str>,
-        country: Option<&str>,
+        filter: &AlbumFilter,
         order_by: Option<&str>,
+        page: Page,
     ) -> Result<Vec<Album>> {
-        let conn = self.conn.lock().await;
-        let base_query = "
-            SELECT id, artist, album, genre, release_date, format, source_url, country, artwork_url 
-            FROM albums 
-            WHERE 1=1";
-
-        let mut sql = String::from(base_query);
-        let mut params_values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-        let mut add_filter = |condition: &str, value: Box<dyn rusqlite::ToSql>| {
-            sql.push_str(condition);
-            params_values.push(value);
-        };
-
-        if let Some(year_val) = year {
-            add_filter(
-                " AND strftime('%Y', release_date) = ?",
-                Box::new(year_val.to_string()),
+        let filter = filter.clone();
+        let order_by = order_by.map(str::to_string);
+        self.call(move |conn| {
+            let (conditions, mut params_values) = filter_conditions(&filter);
+            let mut sql = format!(
+                "SELECT {} FROM albums WHERE 1=1{}",
+                ALBUM_COLUMNS, conditions
             );
-        }
+            let sort_keys = sort_keys(order_by.as_deref());
 
-        if let Some(artist_val) = artist {
-            add_filter(" AND artist LIKE ?", Box::new(format!("%{}%", artist_val)));
-        }
+            if let Some(after) = page.after {
+                let exists: bool = conn.query_row(
+                    "SELECT EXISTS (SELECT 1 FROM albums WHERE id = ?)",
+                    [after],
+                    |row| row.get(0),
+                )?;
+                if !exists {
+                    return Err(GnedbyError::NotFound(format!(
+                        "Album with ID {} not found",
+                        after
+                    )));
+                }
+
+                let condition = keyset_condition(sort_keys);
+                for _ in 0..condition.matches('?').count() {
+                    params_values.push(Box::new(after));
+                }
+                sql.push_str(&condition);
+            }
+
+            let order: Vec<String> = sort_keys
+                .iter()
+                .map(|key| {
+                    format!(
+                        "{}{}",
+                        key.expr(),
+                        if key.descending { " DESC" } else { "" }
+                    )
+                })
+                .collect();
+            sql.push_str(&format!(" ORDER BY {} LIMIT ? OFFSET ?", order.join(", ")));
+            params_values.push(Box::new(page.limit.map_or(-1, i64::from)));
+            params_values.push(Box::new(page.offset));
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let params_iter = params_values.iter().map(|p| p.as_ref());
+
+            let album_rows =
+                stmt.query_map(rusqlite::params_from_iter(params_iter), album_from_row)?;
+
+            let mut albums = Vec::new();
+            for album_result in album_rows {
+                albums.push(album_result?);
+            }
+            load_artist_credits(conn, &mut albums)?;
+            load_album_genres(conn, &mut albums)?;
+
+            Ok(albums)
+        })
+        .await
+    }
+
+    /// Counts the albums matching `filter`, ignoring pagination.
+    pub async fn count_albums(&self, filter: &AlbumFilter) -> Result<i64> {
+        let filter = filter.clone();
+        self.call(move |conn| {
+            let (conditions, params_values) = filter_conditions(&filter);
+            let sql = format!("SELECT COUNT(*) FROM albums WHERE 1=1{}", conditions);
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let params_iter = params_values.iter().map(|p| p.as_ref());
+            let count =
+                stmt.query_row(rusqlite::params_from_iter(params_iter), |row| row.get(0))?;
+            Ok(count)
+        })
+        .await
+    }
+
+    /// Runs the `gnedby doctor` checks against the database.
+    pub async fn find_issues(&self) -> Result<Vec<Issue>> {
+        self.call(|conn| find_issues(conn)).await
+    }
+
+    pub async fn apply_repair(&self, repair: &Repair) -> Result<()> {
+        let repair = repair.clone();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            apply_repair(&tx, &repair)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Counts albums per artist, year, genre, format or origin country, ordered
+    /// by year for `StatsGroup::Year` and by count otherwise.
+    pub async fn get_stats(&self, group: StatsGroup) -> Result<Vec<(String, i64)>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(stats_query(group))?;
+            let rows = stmt.query_map([], |row| {
+                let key: Option<String> = row.get(0)?;
+                let count: i64 = row.get(1)?;
+                Ok((key, count))
+            })?;
+
+            let mut stats = Vec::new();
+            for row_result in rows {
+                let (key, count) = row_result?;
+                let label = match (group, key) {
+                    (StatsGroup::Country, Some(code)) => country_name(&code).to_string(),
+                    (_, Some(key)) => key,
+                    (_, None) => "Unknown".to_string(),
+                };
+                stats.push((label, count));
+            }
+
+            Ok(stats)
+        })
+        .await
+    }
+
+    pub async fn delete_album(&self, id: i64) -> Result<()> {
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let rows_affected = tx.execute("DELETE FROM albums WHERE id = ?", [id])?;
+
+            if rows_affected == 0 {
+                return Err(GnedbyError::NotFound(format!(
+                    "Album with ID {} not found",
+                    id
+                )));
+            }
+
+            tx.execute("DELETE FROM album_artists WHERE album_id = ?", [id])?;
+            tx.execute("DELETE FROM tracks WHERE album_id = ?", [id])?;
+            tx.execute("DELETE FROM album_genres WHERE album_id = ?", [id])?;
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_album_by_id(&self, id: i64) -> Result<Option<Album>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM albums WHERE id = ?",
+                ALBUM_COLUMNS
+            ))?;
+
+            let album = stmt.query_row([id], album_from_row).optional()?;
+
+            match album {
+                Some(album) => {
+                    let mut albums = vec![album];
+                    load_artist_credits(conn, &mut albums)?;
+                    load_album_genres(conn, &mut albums)?;
+                    Ok(albums.pop())
+                }
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    pub async fn get_all_albums(&self) -> Result<Vec<Album>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(&format!("SELECT {} FROM albums", ALBUM_COLUMNS))?;
+
+            let albums = stmt.query_map([], album_from_row)?;
+
+            let mut result = Vec::new();
+            for album in albums {
+                result.push(album?);
+            }
+            load_artist_credits(conn, &mut result)?;
+            load_album_genres(conn, &mut result)?;
+            Ok(result)
+        })
+        .await
+    }
+
+    pub async fn replace_tracks(&self, album_id: i64, tracks: &[Track]) -> Result<()> {
+        let tracks = tracks.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM tracks WHERE album_id = ?", [album_id])?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT OR REPLACE INTO tracks (album_id, disc_number, track_number, title, duration_ms, title_key)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                for track in tracks {
+                    stmt.execute((
+                        album_id,
+                        track.disc_number,
+                        track.track_number,
+                        &track.title,
+                        track.duration_ms,
+                        search_key(&track.title),
+                    ))?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Rewrites every stored Apple artwork URL to `size` pixels, returning how
+    /// many albums changed.
+    pub async fn resize_artwork(&self, size: u32) -> Result<usize> {
+        self.call(move |conn| resize_artwork(conn, "%mzstatic.com/%", size))
+            .await
+    }
+
+    pub async fn get_tracks(&self, album_id: i64) -> Result<Vec<Track>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT disc_number, track_number, title, duration_ms
+             FROM tracks
+             WHERE album_id = ?
+             ORDER BY disc_number, track_number",
+            )?;
+
+            let rows = stmt.query_map([album_id], track_from_row)?;
+
+            let mut tracks = Vec::new();
+            for track in rows {
+                tracks.push(track?);
+            }
+            Ok(tracks)
+        })
+        .await
+    }
+
+    /// Finds albums containing a track whose title matches `title`.
+    pub async fn find_tracks(&self, title: &str) -> Result<Vec<(Album, Track)>> {
+        let title = title.to_string();
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {}, t.disc_number, t.track_number, t.title, t.duration_ms
+                 FROM tracks t
+                 JOIN albums ON albums.id = t.album_id
+                 WHERE fold(t.title) LIKE ?1 OR t.title_key LIKE ?2
+                 ORDER BY albums.artist COLLATE UNICODE, albums.album COLLATE UNICODE, t.disc_number, t.track_number",
+                qualified_album_columns()
+            ))?;
+
+            // The track columns follow the album columns.
+            let offset = ALBUM_COLUMNS.split(", ").count();
+            let params = (format!("%{}%", fold(&title)), search_pattern(&title));
+            let rows = stmt.query_map(params, |row| {
+                let album = album_from_row(row)?;
+                let track = Track {
+                    disc_number: row.get(offset)?,
+                    track_number: row.get(offset + 1)?,
+                    title: row.get(offset + 2)?,
+                    duration_ms: row.get(offset + 3)?,
+                };
+                Ok((album, track))
+            })?;
+
+            let mut matches = Vec::new();
+            for row in rows {
+                matches.push(row?);
+            }
+            Ok(matches)
+        })
+        .await
+    }
+
+    pub async fn list_genres(&self) -> Result<Vec<Genre>> {
+        self.call(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT genres.id, genres.name, parent.name,
+                    (SELECT COUNT(*) FROM album_genres WHERE genre_id = genres.id)
+             FROM genres
+             LEFT JOIN genres parent ON parent.id = genres.parent_id
+             ORDER BY genres.name COLLATE UNICODE",
+            )?;
+
+            let rows = stmt.query_map([], |row| {
+                Ok(Genre {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent: row.get(2)?,
+                    album_count: row.get(3)?,
+                })
+            })?;
+
+            let mut genres = Vec::new();
+            for genre in rows {
+                genres.push(genre?);
+            }
+            Ok(genres)
+        })
+        .await
+    }
+
+    /// Maps `alias` onto `genre`. If the alias already names a separate genre,
+    /// that genre is merged into the target and its albums are moved over.
+    pub async fn alias_genre(&self, alias: &str, genre: &str) -> Result<()> {
+        let alias = alias.to_string();
+        let genre = genre.to_string();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let target_id = resolve_genre(&tx, &genre)?;
+
+            let existing_id: Option<i64> = tx
+                .query_row(
+                    "SELECT genre_id FROM genre_aliases WHERE alias = ?",
+                    [genre_key(&alias)],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if let Some(source_id) = existing_id.filter(|id| *id != target_id) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO album_genres (album_id, genre_id, position)
+                 SELECT album_id, ?1, position FROM album_genres WHERE genre_id = ?2",
+                    (target_id, source_id),
+                )?;
+                tx.execute("DELETE FROM album_genres WHERE genre_id = ?", [source_id])?;
+                tx.execute(
+                    "UPDATE genre_aliases SET genre_id = ?1 WHERE genre_id = ?2",
+                    (target_id, source_id),
+                )?;
+                tx.execute(
+                    "UPDATE genres SET parent_id = ?1 WHERE parent_id = ?2",
+                    (target_id, source_id),
+                )?;
+                tx.execute("DELETE FROM genres WHERE id = ?", [source_id])?;
+            }
+
+            tx.execute(
+                "INSERT OR REPLACE INTO genre_aliases (alias, genre_id) VALUES (?1, ?2)",
+                (genre_key(&alias), target_id),
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_genre_parent(&self, genre: &str, parent: Option<&str>) -> Result<()> {
+        let genre = genre.to_string();
+        let parent = parent.map(str::to_string);
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let genre_id = find_genre(&tx, &genre)?
+                .ok_or_else(|| GnedbyError::NotFound(format!("Genre \"{}\" not found", genre)))?;
+
+            let parent_id = match parent {
+                Some(parent) => {
+                    let parent_id = resolve_genre(&tx, &parent)?;
+                    let creates_cycle: bool = tx.query_row(
+                        "WITH RECURSIVE ancestors(id) AS (
+                            SELECT ?1
+                            UNION
+                            SELECT genres.parent_id FROM genres JOIN ancestors ON genres.id = ancestors.id
+                            WHERE genres.parent_id IS NOT NULL
+                        )
+                        SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2)",
+                        (parent_id, genre_id),
+                        |row| row.get(0),
+                    )?;
+                    if creates_cycle {
+                        return Err(GnedbyError::Validation(format!(
+                            "\"{}\" cannot be nested under \"{}\"",
+                            genre, parent
+                        )));
+                    }
+                    Some(parent_id)
+                }
+                None => None,
+            };
+
+            tx.execute(
+                "UPDATE genres SET parent_id = ?1 WHERE id = ?2",
+                (parent_id, genre_id),
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn update_album_genres(&self, album_id: i64, genres: &[String]) -> Result<()> {
+        let genres = genres.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            let rows_affected = tx.execute(
+                "UPDATE albums SET genre = ?1 WHERE id = ?2",
+                (genres.join(", "), album_id),
+            )?;
 
-        if let Some(genre_val) = genre {
-            add_filter(" AND genre LIKE ?", Box::new(format!("%{}%", genre_val)));
+            if rows_affected == 0 {
+                return Err(GnedbyError::NotFound(format!(
+                    "Album with ID {} not found",
+                    album_id
+                )));
+            }
+
+            set_album_genres(&tx, album_id, &genres)?;
+            mark_edited(&tx, album_id, "genre")?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn open_connection(path: &Path, key: Option<&str>) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        apply_key(&conn, key)?;
+    }
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    register_text_functions(&conn)?;
+    Ok(conn)
+}
+
+/// Folds the write-ahead log back into the main database file, so that the file
+/// alone holds every committed change. Needed before the file is hashed, copied
+/// or uploaded.
+pub fn checkpoint_database(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let conn = open_database_file(path)?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// Opens a plain connection to the database file at `path`, unlocking it when
+/// encrypted, for maintenance work outside of `Database`.
+pub fn open_database_file(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key_for(path)? {
+        apply_key(&conn, &key)?;
+    }
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Opens the database file read-only, without running migrations, so that
+/// `gnedby doctor` can inspect a database that fails to migrate.
+pub fn open_database_read_only(path: &Path) -> Result<Connection> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    if let Some(key) = key_for(path)? {
+        apply_key(&conn, &key)?;
+    }
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Removes the `-wal` and `-shm` files that belong to `path`. Used when the
+/// database file is replaced wholesale, so stale log pages are not applied to it.
+pub fn remove_wal_files(path: &Path) -> Result<()> {
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match fs::remove_file(PathBuf::from(sidecar)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
         }
+    }
+    Ok(())
+}
+
+const ALBUM_COLUMNS: &str = "id, artist, album, genre, release_date, format, source_url, storefront, origin_country, artwork_url, compilation, provider, source_id, label, catalog_number, barcode, format_details, edited_fields";
+
+/// Builds the `AND ...` conditions and parameters shared by `list_albums` and
+/// `count_albums`.
+fn filter_conditions(filter: &AlbumFilter) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut sql = String::new();
+    let mut params_values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    let mut add_filter = |condition: &str, values: Vec<Box<dyn rusqlite::ToSql>>| {
+        sql.push_str(condition);
+        params_values.extend(values);
+    };
+
+    if let Some(year_val) = filter.year {
+        add_filter(" AND release_year = ?", vec![Box::new(year_val)]);
+    }
+
+    if let Some(artist_val) = &filter.artist {
+        // Matches the display artist as well as individual credits, so albums
+        // whose credits were split differently are still found.
+        let folded = format!("%{}%", fold(artist_val));
+        let pattern = search_pattern(artist_val);
+        add_filter(
+            " AND (
+                fold(artist) LIKE ? OR artist_key LIKE ?
+                OR id IN (
+                    SELECT album_id FROM album_artists WHERE fold(name) LIKE ? OR name_key LIKE ?
+                )
+            )",
+            vec![
+                Box::new(folded.clone()),
+                Box::new(pattern.clone()),
+                Box::new(folded),
+                Box::new(pattern),
+            ],
+        );
+    }
+
+    if let Some(genre_val) = &filter.genre {
+        // Matches the genre itself and every genre nested below it.
+        add_filter(
+            " AND id IN (
+                SELECT album_id FROM album_genres WHERE genre_id IN (
+                    WITH RECURSIVE matched(id) AS (
+                        SELECT genre_id FROM genre_aliases WHERE alias LIKE ?
+                        UNION
+                        SELECT genres.id FROM genres JOIN matched ON genres.parent_id = matched.id
+                    )
+                    SELECT id FROM matched
+                )
+            )",
+            vec![Box::new(format!("%{}%", genre_key(genre_val)))],
+        );
+    }
+
+    if let Some(format_val) = filter.format {
+        add_filter(" AND format = ?", vec![Box::new(format_val.as_str())]);
+    }
+
+    if let Some(country_val) = &filter.country {
+        let code = normalize_country(country_val)
+            .map(|c| c.alpha2.to_string())
+            .unwrap_or_else(|| country_val.to_string());
+        add_filter(" AND origin_country = ?", vec![Box::new(code)]);
+    }
 
-        if let Some(format_val) = format {
-            add_filter(" AND format = ?", Box::new(format_val));
+    if let Some(query_val) = &filter.query {
+        let folded = format!("%{}%", fold(query_val));
+        let pattern = search_pattern(query_val);
+        add_filter(
+            " AND (
+                fold(artist) LIKE ? OR fold(album) LIKE ?
+                OR artist_key LIKE ? OR album_key LIKE ?
+                OR id IN (SELECT album_id FROM album_artists WHERE name_key LIKE ?)
+            )",
+            vec![
+                Box::new(folded.clone()),
+                Box::new(folded),
+                Box::new(pattern.clone()),
+                Box::new(pattern.clone()),
+                Box::new(pattern),
+            ],
+        );
+    }
+
+    (sql, params_values)
+}
+
+/// One column of a listing's sort order.
+struct SortKey {
+    column: &'static str,
+    collate: bool,
+    descending: bool,
+}
+
+impl SortKey {
+    const fn asc(column: &'static str) -> Self {
+        SortKey {
+            column,
+            collate: false,
+            descending: false,
         }
+    }
 
-        if let Some(country_val) = country {
-            add_filter(" AND country = ?", Box::new(country_val));
+    const fn text(column: &'static str) -> Self {
+        SortKey {
+            column,
+            collate: true,
+            descending: false,
         }
+    }
 
-        match order_by.unwrap_or("id") {
-            "album" => sql.push_str(" ORDER BY album, artist"),
-            "artist" => sql.push_str(" ORDER BY artist, album"),
-            "year" => sql.push_str(" ORDER BY release_date DESC, artist, album"),
-            _ => sql.push_str(" ORDER BY id"),
+    const fn desc(column: &'static str) -> Self {
+        SortKey {
+            column,
+            collate: false,
+            descending: true,
         }
+    }
 
-        let mut stmt = conn.prepare(&sql)?;
-        let params_iter = params_values.iter().map(|p| p.as_ref());
-
-        let album_rows = stmt.query_map(rusqlite::params_from_iter(params_iter), |row| {
-            Ok(Album {
-                id: Some(row.get(0)?),
-                artist: row.get(1)?,
-                album: row.get(2)?,
-                genre: row.get(3)?,
-                release_date: row.get(4)?,
-                format: row.get(5)?,
-                source_url: row.get(6)?,
-                country: row.get(7)?,
-                artwork_url: row.get(8)?,
-            })
-        })?;
-
-        let mut albums = Vec::new();
-        for album_result in album_rows {
-            albums.push(album_result?);
+    fn expr(&self) -> String {
+        if self.collate {
+            format!("{} COLLATE UNICODE", self.column)
+        } else {
+            self.column.to_string()
         }
+    }
+}
+
+/// Sort keys for each `order_by` value. Every order ends with `id` so that rows
+/// have a stable position, which keyset pagination relies on.
+fn sort_keys(order_by: Option<&str>) -> &'static [SortKey] {
+    const BY_ID: &[SortKey] = &[SortKey::asc("id")];
+    const BY_ALBUM: &[SortKey] = &[
+        SortKey::text("album"),
+        SortKey::text("artist"),
+        SortKey::asc("id"),
+    ];
+    const BY_ARTIST: &[SortKey] = &[
+        SortKey::text("artist"),
+        SortKey::text("album"),
+        SortKey::asc("id"),
+    ];
+    const BY_YEAR: &[SortKey] = &[
+        SortKey::desc("release_date"),
+        SortKey::text("artist"),
+        SortKey::text("album"),
+        SortKey::asc("id"),
+    ];
 
-        Ok(albums)
+    match order_by.unwrap_or("id") {
+        "album" => BY_ALBUM,
+        "artist" => BY_ARTIST,
+        "year" => BY_YEAR,
+        _ => BY_ID,
     }
+}
+
+/// Matches rows that sort after the cursor album, whose ID is bound to every
+/// `?` in the returned condition.
+fn keyset_condition(keys: &[SortKey]) -> String {
+    let cursor = |key: &SortKey| format!("(SELECT {} FROM albums WHERE id = ?)", key.column);
 
-    pub async fn get_artist_stats(&self) -> Result<Vec<(String, i64)>> {
-        let conn = self.conn.lock().await;
-        let sql = "
-            SELECT artist, COUNT(*) as count 
-            FROM albums 
-            GROUP BY artist 
-            ORDER BY count DESC
-        ";
+    let alternatives: Vec<String> = (0..keys.len())
+        .map(|i| {
+            let mut terms: Vec<String> = keys[..i]
+                .iter()
+                .map(|key| format!("{} = {}", key.expr(), cursor(key)))
+                .collect();
+            let key = &keys[i];
+            let op = if key.descending { "<" } else { ">" };
+            terms.push(format!("{} {} {}", key.expr(), op, cursor(key)));
+            format!("({})", terms.join(" AND "))
+        })
+        .collect();
 
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            let artist: String = row.get(0)?;
-            let count: i64 = row.get(1)?;
-            Ok((artist, count))
-        })?;
+    format!(" AND ({})", alternatives.join(" OR "))
+}
 
-        let mut stats = Vec::new();
-        for row_result in rows {
-            stats.push(row_result?);
+/// Grouped-count query behind `Database::get_stats`. Every query yields the
+/// group key as text (NULL when unknown) and the number of albums in it.
+fn stats_query(group: StatsGroup) -> &'static str {
+    match group {
+        StatsGroup::Artist => {
+            "SELECT name, COUNT(DISTINCT album_id) AS count
+             FROM album_artists
+             WHERE role != 'featuring'
+             GROUP BY name
+             ORDER BY count DESC"
+        }
+        StatsGroup::Year => {
+            "SELECT CAST(release_year AS TEXT), COUNT(*)
+             FROM albums
+             GROUP BY release_year
+             ORDER BY release_year ASC"
         }
+        StatsGroup::Genre { top_level: false } => {
+            "SELECT genres.name, COUNT(DISTINCT album_genres.album_id) AS count
+             FROM album_genres
+             JOIN genres ON genres.id = album_genres.genre_id
+             GROUP BY genres.id
+             ORDER BY count DESC"
+        }
+        StatsGroup::Genre { top_level: true } => {
+            "WITH RECURSIVE roots(id, root_id) AS (
+                SELECT id, id FROM genres WHERE parent_id IS NULL
+                UNION ALL
+                SELECT genres.id, roots.root_id FROM genres JOIN roots ON genres.parent_id = roots.id
+             )
+             SELECT root.name, COUNT(DISTINCT album_genres.album_id) AS count
+             FROM album_genres
+             JOIN roots ON roots.id = album_genres.genre_id
+             JOIN genres root ON root.id = roots.root_id
+             GROUP BY roots.root_id
+             ORDER BY count DESC"
+        }
+        StatsGroup::Format => {
+            "SELECT format, COUNT(*) AS count
+             FROM albums
+             GROUP BY format
+             ORDER BY count DESC"
+        }
+        StatsGroup::Country => {
+            "SELECT origin_country, COUNT(*) AS count
+             FROM albums
+             GROUP BY origin_country
+             ORDER BY count DESC"
+        }
+    }
+}
+
+fn album_from_row(row: &Row) -> rusqlite::Result<Album> {
+    Ok(Album {
+        id: Some(row.get(0)?),
+        artist: row.get(1)?,
+        album: row.get(2)?,
+        genre: row.get(3)?,
+        release_date: row.get(4)?,
+        format: row.get(5)?,
+        source_url: row.get(6)?,
+        storefront: row.get(7)?,
+        origin_country: row.get(8)?,
+        artwork_url: row.get(9)?,
+        artists: Vec::new(),
+        compilation: row.get(10)?,
+        provider: row.get(11)?,
+        source_id: row.get(12)?,
+        label: row.get(13)?,
+        catalog_number: row.get(14)?,
+        barcode: row.get(15)?,
+        format_details: row.get(16)?,
+        genres: Vec::new(),
+        edited_fields: row
+            .get::<_, Option<String>>(17)?
+            .map(|fields| fields.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+    })
+}
 
-        Ok(stats)
+fn qualified_album_columns() -> String {
+    ALBUM_COLUMNS
+        .split(", ")
+        .map(|column| format!("albums.{}", column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn track_from_row(row: &Row) -> rusqlite::Result<Track> {
+    Ok(Track {
+        disc_number: row.get(0)?,
+        track_number: row.get(1)?,
+        title: row.get(2)?,
+        duration_ms: row.get(3)?,
+    })
+}
+
+pub(crate) fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_xinfo({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
     }
+    Ok(())
+}
 
-    pub async fn get_year_stats(&self) -> Result<Vec<(String, i64)>> {
-        let conn = self.conn.lock().await;
-        let sql = "
-            SELECT strftime('%Y', release_date) as year, COUNT(*) as count 
-            FROM albums 
-            GROUP BY year 
-            ORDER BY year ASC
-        ";
+fn create_indexes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_albums_release_year ON albums (release_year);
+         CREATE INDEX IF NOT EXISTS idx_albums_artist ON albums (artist);
+         CREATE INDEX IF NOT EXISTS idx_albums_format ON albums (format);
+         CREATE INDEX IF NOT EXISTS idx_albums_origin_country ON albums (origin_country);
+         CREATE INDEX IF NOT EXISTS idx_album_artists_name ON album_artists (name, album_id);
+         CREATE INDEX IF NOT EXISTS idx_album_genres_genre ON album_genres (genre_id, album_id);
+         CREATE INDEX IF NOT EXISTS idx_genres_parent ON genres (parent_id);
+         CREATE INDEX IF NOT EXISTS idx_genre_aliases_genre ON genre_aliases (genre_id);",
+    )?;
+    Ok(())
+}
 
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            let year: String = row.get(0)?;
-            let count: i64 = row.get(1)?;
-            Ok((year, count))
-        })?;
+/// Splits the old free-text `country` column. Apple lookups stored the storefront
+/// there, while manual entries stored where the album comes from. The column is
+/// kept, mirroring the storefront or else the origin country, for older versions
+/// that still read it, e.g. on another device through sync.
+fn migrate_legacy_country(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "albums", "country", "TEXT")?;
 
-        let mut stats = Vec::new();
-        for row_result in rows {
-            stats.push(row_result?);
-        }
+    // Rows written by older versions only have `country`.
+    let mut stmt = conn.prepare(
+        "SELECT id, country, source_url FROM albums
+         WHERE trim(country) != '' AND storefront IS NULL AND origin_country IS NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, country, source_url) in rows {
+        let code = normalize_country(&country)
+            .map(|c| c.alpha2.to_string())
+            .unwrap_or_else(|| country.trim().to_string());
 
-        Ok(stats)
+        let column = if source_url.is_some_and(|url| url.contains("apple.com")) {
+            "storefront"
+        } else {
+            "origin_country"
+        };
+        conn.execute(
+            &format!("UPDATE albums SET {} = ?1 WHERE id = ?2", column),
+            (code, id),
+        )?;
     }
 
-    pub async fn get_genre_stats(&self) -> Result<Vec<(String, i64)>> {
-        let conn = self.conn.lock().await;
-        let sql = "
-            SELECT genre, COUNT(*) as count 
-            FROM albums 
-            GROUP BY genre 
-            ORDER BY count DESC
-        ";
+    conn.execute_batch(
+        "UPDATE albums SET country = COALESCE(storefront, origin_country, '')
+         WHERE country IS NOT COALESCE(storefront, origin_country, '');
+         CREATE TRIGGER IF NOT EXISTS albums_legacy_country_insert AFTER INSERT ON albums
+         WHEN NEW.country IS NULL
+         BEGIN
+             UPDATE albums SET country = COALESCE(NEW.storefront, NEW.origin_country, '')
+             WHERE id = NEW.id;
+         END;
+         CREATE TRIGGER IF NOT EXISTS albums_legacy_country_update
+         AFTER UPDATE OF storefront, origin_country ON albums
+         BEGIN
+             UPDATE albums SET country = COALESCE(NEW.storefront, NEW.origin_country, '')
+             WHERE id = NEW.id;
+         END;",
+    )?;
+    Ok(())
+}
 
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            let genre: String = row.get(0)?;
-            let count: i64 = row.get(1)?;
-            Ok((genre, count))
-        })?;
+/// Empties search keys of text without Hangul or kana, which older versions
+/// filled with a phonetic key that matched unrelated Latin names.
+fn clear_latin_search_keys(conn: &Connection) -> Result<()> {
+    for (table, key, text) in [
+        ("albums", "artist_key", "artist"),
+        ("albums", "album_key", "album"),
+        ("album_artists", "name_key", "name"),
+        ("tracks", "title_key", "title"),
+    ] {
+        conn.execute(
+            &format!(
+                "UPDATE {} SET {} = '' WHERE {} != '' AND {} NOT GLOB ?1",
+                table, key, key, text
+            ),
+            [ROMANIZED_GLOB],
+        )?;
+    }
+    Ok(())
+}
 
-        let mut stats = Vec::new();
-        for row_result in rows {
-            stats.push(row_result?);
-        }
+/// Fills romanized search keys for rows that were written without them, e.g. by
+/// an older version or another device through sync.
+fn rebuild_search_keys(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, artist, album FROM albums WHERE artist_key IS NULL OR album_key IS NULL",
+    )?;
+    let albums = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, artist, album) in albums {
+        conn.execute(
+            "UPDATE albums SET artist_key = ?1, album_key = ?2 WHERE id = ?3",
+            (search_key(&artist), search_key(&album), id),
+        )?;
+    }
+
+    let mut stmt =
+        conn.prepare("SELECT album_id, position, name FROM album_artists WHERE name_key IS NULL")?;
+    let credits = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (album_id, position, name) in credits {
+        conn.execute(
+            "UPDATE album_artists SET name_key = ?1 WHERE album_id = ?2 AND position = ?3",
+            (search_key(&name), album_id, position),
+        )?;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT album_id, disc_number, track_number, title FROM tracks WHERE title_key IS NULL",
+    )?;
+    let tracks = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (album_id, disc_number, track_number, title) in tracks {
+        conn.execute(
+            "UPDATE tracks SET title_key = ?1
+             WHERE album_id = ?2 AND disc_number = ?3 AND track_number = ?4",
+            (search_key(&title), album_id, disc_number, track_number),
+        )?;
+    }
+    Ok(())
+}
 
-        Ok(stats)
+pub(crate) fn insert_artist_credits(
+    conn: &Connection,
+    album_id: i64,
+    credits: &[ArtistCredit],
+) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO album_artists (album_id, position, name, role, name_key)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (position, credit) in credits.iter().enumerate() {
+        stmt.execute((
+            album_id,
+            position as i64,
+            &credit.name,
+            credit.role.as_str(),
+            search_key(&credit.name),
+        ))?;
     }
+    Ok(())
+}
+
+/// Encodes the IDs of `albums` as a JSON array for `json_each`, so that related
+/// rows are loaded only for the albums being returned.
+fn album_ids_json(albums: &[Album]) -> Result<String> {
+    let ids: Vec<i64> = albums.iter().filter_map(|album| album.id).collect();
+    Ok(serde_json::to_string(&ids)?)
+}
 
-    pub async fn get_format_stats(&self) -> Result<Vec<(String, i64)>> {
-        let conn = self.conn.lock().await;
-        let sql = "
-            SELECT format, COUNT(*) as count 
-            FROM albums 
-            GROUP BY format 
-            ORDER BY count DESC
-        ";
+fn load_artist_credits(conn: &Connection, albums: &mut [Album]) -> Result<()> {
+    if albums.is_empty() {
+        return Ok(());
+    }
 
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            let format: String = row.get(0)?;
-            let count: i64 = row.get(1)?;
-            Ok((format, count))
-        })?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT album_id, name, role FROM album_artists
+         WHERE album_id IN (SELECT value FROM json_each(?))
+         ORDER BY album_id, position",
+    )?;
+    let rows = stmt.query_map([album_ids_json(albums)?], |row| {
+        let album_id: i64 = row.get(0)?;
+        let name: String = row.get(1)?;
+        let role: ArtistRole = row.get(2)?;
+        Ok((album_id, ArtistCredit { name, role }))
+    })?;
 
-        let mut stats = Vec::new();
-        for row_result in rows {
-            stats.push(row_result?);
-        }
+    let mut credits: HashMap<i64, Vec<ArtistCredit>> = HashMap::new();
+    for row in rows {
+        let (album_id, credit) = row?;
+        credits.entry(album_id).or_default().push(credit);
+    }
 
-        Ok(stats)
+    for album in albums.iter_mut() {
+        if let Some(album_credits) = album.id.and_then(|id| credits.remove(&id)) {
+            album.artists = album_credits;
+        }
     }
+    Ok(())
+}
 
-    pub async fn get_country_stats(&self) -> Result<Vec<(String, i64)>> {
-        let conn = self.conn.lock().await;
-        let sql = "
-            SELECT country, COUNT(*) as count 
-            FROM albums 
-            GROUP BY country 
-            ORDER BY count DESC
-        ";
+/// Populates `album_artists` for rows created before credits existed, and flags
+/// "Various Artists" rows as compilations.
+fn backfill_artist_credits(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, artist FROM albums WHERE id NOT IN (SELECT album_id FROM album_artists)",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        let mut stmt = conn.prepare(sql)?;
-        let rows = stmt.query_map([], |row| {
-            let country: String = row.get(0)?;
-            let count: i64 = row.get(1)?;
-            Ok((country, count))
-        })?;
+    for (id, artist) in rows {
+        let credits = ArtistCredit::parse(&artist);
+        insert_artist_credits(conn, id, &credits)?;
+        if credits.iter().any(|c| c.role == ArtistRole::Various) {
+            conn.execute("UPDATE albums SET compilation = 1 WHERE id = ?", [id])?;
+        }
+    }
+    Ok(())
+}
 
-        let mut stats = Vec::new();
-        for row_result in rows {
-            stats.push(row_result?);
+/// Rebuilds credits that were split on every `,` and `&` of the display artist,
+/// which broke band names such as "Earth, Wind & Fire" apart. Credits that came
+/// from a provider's structured artist list don't match that split and are kept.
+fn rebuild_split_artist_credits(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT albums.id, albums.artist, album_artists.name
+         FROM albums JOIN album_artists ON album_artists.album_id = albums.id
+         ORDER BY albums.id, album_artists.position",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut albums: Vec<(i64, String, Vec<String>)> = Vec::new();
+    for (id, artist, name) in rows {
+        match albums.last_mut() {
+            Some((last_id, _, names)) if *last_id == id => names.push(name),
+            _ => albums.push((id, artist, vec![name])),
         }
+    }
 
-        Ok(stats)
+    for (id, artist, names) in albums {
+        let credits = ArtistCredit::parse(&artist);
+        let parsed: Vec<&str> = credits.iter().map(|c| c.name.as_str()).collect();
+        if names == ArtistCredit::legacy_names(&artist) && names != parsed {
+            conn.execute("DELETE FROM album_artists WHERE album_id = ?", [id])?;
+            insert_artist_credits(conn, id, &credits)?;
+        }
     }
+    Ok(())
+}
 
-    pub async fn delete_album(&self, id: i64) -> Result<()> {
-        let conn = self.conn.lock().await;
-        let rows_affected = conn.execute("DELETE FROM albums WHERE id = ?", [id])?;
+/// Records Apple Music as the provider of albums added before providers were
+/// tracked, taking the album id from the source URL.
+fn backfill_album_sources(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT id, source_url FROM albums WHERE provider IS NULL")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        if rows_affected == 0 {
-            return Err(anyhow::anyhow!("Album with ID {} not found", id));
+    for (id, source_url) in rows {
+        if let Some(apple_id) = source_url.as_deref().and_then(apple_album_id_from_url) {
+            conn.execute(
+                "UPDATE albums SET provider = 'apple', source_id = ?1 WHERE id = ?2",
+                (apple_id, id),
+            )?;
         }
+    }
+    Ok(())
+}
 
-        Ok(())
+/// Apple lookups used to store the API's 100×100 thumbnails; point them at
+/// the configured artwork size instead.
+fn upgrade_artwork_thumbnails(conn: &Connection) -> Result<()> {
+    const THUMBNAILS: &str = "%mzstatic.com/%/100x100%";
+    let has_thumbnails: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM albums WHERE artwork_url LIKE ?)",
+        [THUMBNAILS],
+        |row| row.get(0),
+    )?;
+    if has_thumbnails {
+        // Read the config only when there is work to do, so opening a
+        // database doesn't depend on it.
+        let size = load_metadata_config()
+            .map(|config| config.artwork_size)
+            .unwrap_or(DEFAULT_ARTWORK_SIZE);
+        resize_artwork(conn, THUMBNAILS, size)?;
     }
+    Ok(())
+}
 
-    pub async fn get_album_by_id(&self, id: i64) -> Result<Option<Album>> {
-        let conn = self.conn.lock().await;
-        let mut stmt = conn.prepare(
-            "SELECT id, artist, album, genre, release_date, format, source_url, country, artwork_url 
-             FROM albums 
-             WHERE id = ?"
+/// Rewrites the size of Apple artwork URLs matching `pattern` (a LIKE pattern)
+/// to `size`, returning how many albums changed.
+fn resize_artwork(conn: &Connection, pattern: &str, size: u32) -> Result<usize> {
+    let mut stmt = conn.prepare("SELECT id, artwork_url FROM albums WHERE artwork_url LIKE ?")?;
+    let rows = stmt
+        .query_map([pattern], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut changed = 0;
+    for (id, url) in rows {
+        let resized = apple_artwork_url(&url, size);
+        if resized != url {
+            conn.execute(
+                "UPDATE albums SET artwork_url = ?1 WHERE id = ?2",
+                (resized, id),
+            )?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+fn edited_fields_value(fields: &[String]) -> Option<String> {
+    Some(fields.join(",")).filter(|fields| !fields.is_empty())
+}
+
+/// Records that a field of an album was changed by hand, so that
+/// `gnedby refresh` leaves it alone.
+pub(crate) fn mark_edited(conn: &Connection, album_id: i64, field: &str) -> Result<()> {
+    let fields: Option<String> = conn
+        .query_row(
+            "SELECT edited_fields FROM albums WHERE id = ?",
+            [album_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let mut fields: Vec<String> = fields
+        .map(|fields| fields.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    if !fields.iter().any(|edited| edited == field) {
+        fields.push(field.to_string());
+        conn.execute(
+            "UPDATE albums SET edited_fields = ?1 WHERE id = ?2",
+            (edited_fields_value(&fields), album_id),
         )?;
+    }
+    Ok(())
+}
 
-        let album = stmt
-            .query_row([id], |row| {
-                Ok(Album {
-                    id: Some(row.get(0)?),
-                    artist: row.get(1)?,
-                    album: row.get(2)?,
-                    genre: row.get(3)?,
-                    release_date: row.get(4)?,
-                    format: row.get(5)?,
-                    source_url: row.get(6)?,
-                    country: row.get(7)?,
-                    artwork_url: row.get(8)?,
-                })
-            })
-            .optional()?;
+fn find_genre(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    let id = conn
+        .query_row(
+            "SELECT genre_id FROM genre_aliases WHERE alias = ?",
+            [genre_key(name)],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id)
+}
 
-        Ok(album)
+/// Looks up a genre through its aliases, creating it when it is unknown.
+fn resolve_genre(conn: &Connection, name: &str) -> Result<i64> {
+    if let Some(id) = find_genre(conn, name)? {
+        return Ok(id);
     }
 
-    pub async fn get_all_albums(&self) -> Result<Vec<Album>> {
-        let conn = self.conn.lock().await;
-        let mut stmt = conn.prepare(
-            "SELECT id, artist, album, genre, release_date, format, source_url, country, artwork_url FROM albums",
+    conn.execute("INSERT INTO genres (name) VALUES (?)", [name.trim()])?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT OR REPLACE INTO genre_aliases (alias, genre_id) VALUES (?1, ?2)",
+        (genre_key(name), id),
+    )?;
+    Ok(id)
+}
+
+fn set_album_genres(conn: &Connection, album_id: i64, genres: &[String]) -> Result<()> {
+    conn.execute("DELETE FROM album_genres WHERE album_id = ?", [album_id])?;
+    for (position, genre) in genres.iter().enumerate() {
+        if genre_key(genre).is_empty() {
+            continue;
+        }
+        let genre_id = resolve_genre(conn, genre)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO album_genres (album_id, genre_id, position) VALUES (?1, ?2, ?3)",
+            (album_id, genre_id, position as i64),
         )?;
+    }
+    Ok(())
+}
 
-        let albums = stmt.query_map([], |row| {
-            Ok(Album {
-                id: Some(row.get(0)?),
-                artist: row.get(1)?,
-                album: row.get(2)?,
-                genre: row.get(3)?,
-                release_date: row.get(4)?,
-                format: row.get(5)?,
-                source_url: row.get(6)?,
-                country: row.get(7)?,
-                artwork_url: row.get(8)?,
-            })
-        })?;
-
-        let mut result = Vec::new();
-        for album in albums {
-            result.push(album?);
+fn load_album_genres(conn: &Connection, albums: &mut [Album]) -> Result<()> {
+    if albums.is_empty() {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT album_genres.album_id, genres.name
+         FROM album_genres
+         JOIN genres ON genres.id = album_genres.genre_id
+         WHERE album_genres.album_id IN (SELECT value FROM json_each(?))
+         ORDER BY album_genres.album_id, album_genres.position",
+    )?;
+    let rows = stmt.query_map([album_ids_json(albums)?], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut genres: HashMap<i64, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (album_id, name) = row?;
+        genres.entry(album_id).or_default().push(name);
+    }
+
+    for album in albums.iter_mut() {
+        if let Some(album_genres) = album.id.and_then(|id| genres.remove(&id)) {
+            album.genres = album_genres;
         }
-        Ok(result)
     }
+    Ok(())
+}
 
-    pub async fn with_path(path: &Path) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        let db = Database {
-            conn: Mutex::new(conn),
-        };
-        db.init().await?;
-        Ok(db)
+fn seed_genres(conn: &Connection) -> Result<()> {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM genres", [], |row| row.get(0))?;
+    if count > 0 {
+        return Ok(());
+    }
+
+    for (parent, children) in DEFAULT_GENRE_HIERARCHY {
+        let parent_id = resolve_genre(conn, parent)?;
+        for child in children.iter() {
+            let child_id = resolve_genre(conn, child)?;
+            conn.execute(
+                "UPDATE genres SET parent_id = ?1 WHERE id = ?2",
+                (parent_id, child_id),
+            )?;
+        }
     }
+    Ok(())
+}
+
+fn backfill_album_genres(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, genre FROM albums
+         WHERE genre IS NOT NULL AND id NOT IN (SELECT album_id FROM album_genres)",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, genre) in rows {
+        set_album_genres(conn, id, &split_genres(&genre))?;
+    }
+    Ok(())
 }
 
 pub fn get_db_path() -> Result<PathBuf> {
     let project_dirs = ProjectDirs::from("com", "gnedby", "gnedby")
-        .ok_or_else(|| anyhow::anyhow!("Failed to determine data directory"))?;
+        .ok_or_else(|| GnedbyError::Storage("Failed to determine data directory".to_string()))?;
 
     let data_dir = project_dirs.data_dir();
     fs::create_dir_all(data_dir)?;
@@ -354,6 +1595,7 @@ pub fn calculate_db_hash() -> Result<String> {
     if !db_path.exists() {
         return Ok("empty".to_string());
     }
+    checkpoint_database(&db_path)?;
 
     let mut file =
         fs::File::open(&db_path).context(format!("Failed to open database file: {:?}", db_path))?;
@@ -368,3 +1610,78 @@ pub fn calculate_db_hash() -> Result<String> {
 
     Ok(format!("{:x}", result))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Format;
+
+    /// Opens a fresh database in the temp directory, removed on drop.
+    struct TempDb {
+        path: PathBuf,
+        db: Database,
+    }
+
+    impl TempDb {
+        async fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("gnedby-{}-{}.db", name, std::process::id()));
+            let _ = fs::remove_file(&path);
+            let db = Database::with_path(&path).await.unwrap();
+            TempDb { path, db }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = fs::remove_file(format!("{}{}", self.path.display(), suffix));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn find_tracks_returns_album_and_track() {
+        let temp = TempDb::new("find-tracks").await;
+        let album = Album {
+            id: None,
+            artist: "IU".to_string(),
+            album: "Palette".to_string(),
+            genre: "K-Pop".to_string(),
+            release_date: "2017-04-21".to_string(),
+            format: Format::Cd,
+            source_url: String::new(),
+            storefront: Some("KR".to_string()),
+            origin_country: None,
+            artwork_url: String::new(),
+            provider: Some("apple".to_string()),
+            source_id: Some("1223303398".to_string()),
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format_details: None,
+            artists: ArtistCredit::parse("IU"),
+            compilation: false,
+            genres: Vec::new(),
+            edited_fields: Vec::new(),
+        };
+        let id = temp.db.add_album(&album).await.unwrap();
+        let track = Track {
+            disc_number: 1,
+            track_number: 2,
+            title: "Palette".to_string(),
+            duration_ms: Some(217_000),
+        };
+        temp.db
+            .replace_tracks(id, std::slice::from_ref(&track))
+            .await
+            .unwrap();
+
+        let matches = temp.db.find_tracks("palette").await.unwrap();
+        assert_eq!(matches.len(), 1);
+        let (found, found_track) = &matches[0];
+        assert_eq!(found.id, Some(id));
+        assert_eq!(found.provider.as_deref(), Some("apple"));
+        assert_eq!(found_track, &track);
+    }
+}
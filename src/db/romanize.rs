@@ -0,0 +1,233 @@
+use crate::db::text::fold;
+
+const HANGUL_BASE: u32 = 0xAC00;
+const HANGUL_LAST: u32 = 0xD7A3;
+
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Hiragana from U+3041 (ぁ) to U+3096 (ゖ). Small kana are marked with a
+/// leading `_` and combined with the preceding syllable.
+const HIRAGANA: [&str; 86] = [
+    "_a", "a", "_i", "i", "_u", "u", "_e", "e", "_o", "o", // ぁ-お
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go", // か-ご
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", // さ-ぞ
+    "ta", "da", "chi", "ji", "_tsu", "tsu", "zu", "te", "de", "to", "do", // た-ど
+    "na", "ni", "nu", "ne", "no", // な-の
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo",
+    "po", // は-ぽ
+    "ma", "mi", "mu", "me", "mo", // ま-も
+    "_ya", "ya", "_yu", "yu", "_yo", "yo", // ゃ-よ
+    "ra", "ri", "ru", "re", "ro", // ら-ろ
+    "_wa", "wa", "wi", "we", "wo", "n", "vu", "_ka", "_ke", // ゎ-ゖ
+];
+
+/// Transliterates Hangul (Revised Romanization, syllable by syllable) and
+/// kana (Hepburn) into Latin letters. Other characters are folded and kept.
+pub fn romanize(text: &str) -> String {
+    let mut out = String::new();
+    let mut pending_sokuon = false;
+
+    for c in fold(text).chars() {
+        let code = c as u32;
+
+        if (HANGUL_BASE..=HANGUL_LAST).contains(&code) {
+            let index = (code - HANGUL_BASE) as usize;
+            out.push_str(INITIALS[index / 588]);
+            out.push_str(VOWELS[(index % 588) / 28]);
+            out.push_str(FINALS[index % 28]);
+            continue;
+        }
+
+        let Some(romaji) = kana_romaji(c) else {
+            if c == 'ー' {
+                // Long vowel mark: repeat the previous vowel.
+                if let Some(vowel) = out.chars().last().filter(|v| "aeiou".contains(*v)) {
+                    out.push(vowel);
+                }
+            } else {
+                out.push(c);
+            }
+            pending_sokuon = false;
+            continue;
+        };
+
+        if romaji == "_tsu" {
+            pending_sokuon = true;
+            continue;
+        }
+
+        if let Some(small) = romaji.strip_prefix('_') {
+            combine_small_kana(&mut out, small);
+            continue;
+        }
+
+        if pending_sokuon {
+            // Small tsu doubles the next consonant ("ch" becomes "tch").
+            if romaji.starts_with("ch") {
+                out.push('t');
+            } else if let Some(first) = romaji.chars().next().filter(|f| !"aeiou".contains(*f)) {
+                out.push(first);
+            }
+            pending_sokuon = false;
+        }
+        out.push_str(romaji);
+    }
+
+    out
+}
+
+fn kana_romaji(c: char) -> Option<&'static str> {
+    let code = c as u32;
+    let hiragana = match code {
+        0x3041..=0x3096 => code,
+        0x30A1..=0x30F6 => code - 0x60,
+        _ => return None,
+    };
+    Some(HIRAGANA[(hiragana - 0x3041) as usize])
+}
+
+fn combine_small_kana(out: &mut String, small: &str) {
+    let yoon = matches!(small, "ya" | "yu" | "yo");
+    if yoon && out.ends_with('i') {
+        out.pop();
+        // "shi" + "ya" is "sha", but "ki" + "ya" is "kya".
+        if out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j') {
+            out.push_str(&small[1..]);
+        } else {
+            out.push_str(small);
+        }
+    } else if small.len() == 1 && out.ends_with(|c: char| "aeiou".contains(c)) {
+        // Small vowels replace the previous vowel, as in "fa" or "ti".
+        out.pop();
+        out.push_str(small);
+    } else {
+        out.push_str(small);
+    }
+}
+
+/// SQLite `GLOB` pattern matching text with Hangul syllables or kana, the
+/// scripts that get a search key.
+pub(crate) const ROMANIZED_GLOB: &str = "*[가-힣ぁ-ゖァ-ヶｦ-ﾝ]*";
+
+/// Builds the stored search key of `text`, used to match Latin queries against
+/// romanized Hangul and kana. Text without Hangul or kana gets an empty key and
+/// is matched on its folded form instead, since the loose key would make "Ed"
+/// match "Metallica".
+pub fn search_key(text: &str) -> String {
+    let has_romanized_script = fold(text)
+        .chars()
+        .any(|c| (HANGUL_BASE..=HANGUL_LAST).contains(&(c as u32)) || kana_romaji(c).is_some());
+    if has_romanized_script {
+        phonetic_key(text)
+    } else {
+        String::new()
+    }
+}
+
+/// Loose phonetic key of romanized text. Spelling variants such as
+/// "taiji"/"taeji", "seo"/"so", "ch"/"j" and "kyary"/"kyarii" collapse to the
+/// same key, so "seo taiji" finds "서태지".
+fn phonetic_key(text: &str) -> String {
+    let mut key: String = romanize(text)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+
+    const REPLACEMENTS: [(&str, &str); 22] = [
+        ("tch", "c"),
+        ("ch", "c"),
+        ("sh", "s"),
+        ("ph", "p"),
+        ("th", "t"),
+        ("kh", "k"),
+        ("eo", "o"),
+        ("eu", "u"),
+        ("ae", "e"),
+        ("ai", "e"),
+        ("ei", "e"),
+        ("oo", "u"),
+        ("ou", "u"),
+        ("g", "k"),
+        ("d", "t"),
+        ("b", "p"),
+        ("j", "c"),
+        ("r", "l"),
+        ("f", "p"),
+        ("v", "p"),
+        ("z", "s"),
+        ("y", "i"),
+    ];
+    for (from, to) in REPLACEMENTS {
+        key = key.replace(from, to);
+    }
+
+    let mut collapsed = String::with_capacity(key.len());
+    for c in key.chars() {
+        if !collapsed.ends_with(c) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Wraps the phonetic key of a query for a `LIKE` comparison against stored
+/// search keys, or returns `None` when the query has no searchable characters
+/// so that it cannot match everything.
+pub fn search_pattern(text: &str) -> Option<String> {
+    let key = phonetic_key(text);
+    if key.is_empty() {
+        None
+    } else {
+        Some(format!("%{}%", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, text: &str) -> bool {
+        let key = search_key(text);
+        search_pattern(query).is_some_and(|pattern| key.contains(pattern.trim_matches('%')))
+    }
+
+    #[test]
+    fn romanizes_hangul_and_kana() {
+        assert_eq!(romanize("서태지"), "seotaeji");
+        assert_eq!(romanize("きゃりーぱみゅぱみゅ"), "kyariipamyupamyu");
+        assert_eq!(romanize("ロッテ"), "rotte");
+    }
+
+    #[test]
+    fn latin_queries_match_romanized_text() {
+        assert!(matches("seo taiji", "서태지와 아이들"));
+        assert!(matches("so taeji", "서태지"));
+        assert!(matches("kyary pamyu", "きゃりーぱみゅぱみゅ"));
+        assert!(matches("서태지", "서태지"));
+    }
+
+    #[test]
+    fn latin_text_has_no_search_key() {
+        assert_eq!(search_key("Metallica"), "");
+        assert_eq!(search_key("Pet Shop Boys"), "");
+        assert!(!matches("Ed", "Metallica"));
+        assert!(!matches("Ed", "Pet Shop Boys"));
+        assert_eq!(search_key("IU (아이유)"), phonetic_key("IU (아이유)"));
+    }
+
+    #[test]
+    fn empty_queries_match_nothing() {
+        assert_eq!(search_pattern("!?"), None);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::Result;
+use icu_collator::{Collator, CollatorOptions, Strength};
+use icu_normalizer::{ComposingNormalizer, DecomposingNormalizer};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use std::cmp::Ordering;
+
+/// Name of the Unicode-aware collation registered on every connection.
+pub const UNICODE_COLLATION: &str = "UNICODE";
+
+/// Folds text for matching: full-width and compatibility forms become their
+/// plain equivalents, Latin accents are dropped and case is ignored, so "Bjork"
+/// matches "Björk" and "ＩＵ" matches "IU". Kana voicing marks and Hangul are
+/// preserved.
+pub fn fold(text: &str) -> String {
+    let decomposed = DecomposingNormalizer::new_nfkd().normalize(text);
+    let stripped: String = decomposed
+        .chars()
+        .filter(|c| !is_latin_combining_mark(*c))
+        .collect();
+    ComposingNormalizer::new_nfc()
+        .normalize(&stripped)
+        .to_lowercase()
+}
+
+fn is_latin_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+thread_local! {
+    // ICU data payloads are not `Send`, so each SQLite worker thread gets its own
+    // collator built from the compiled-in root collation data.
+    static COLLATOR: Collator = {
+        let mut options = CollatorOptions::new();
+        options.strength = Some(Strength::Tertiary);
+        Collator::try_new(&Default::default(), options)
+            .expect("root collation data is compiled in")
+    };
+}
+
+/// Compares two strings using the Unicode Collation Algorithm's root order,
+/// which sorts Latin, Hangul and Kana sensibly instead of by byte value.
+pub fn unicode_compare(a: &str, b: &str) -> Ordering {
+    COLLATOR.with(|collator| collator.compare(a, b))
+}
+
+/// Registers the `UNICODE` collation and the `fold()` SQL function.
+pub fn register_text_functions(conn: &Connection) -> Result<()> {
+    conn.create_collation(UNICODE_COLLATION, unicode_compare)?;
+
+    conn.create_scalar_function(
+        "fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text: Option<String> = ctx.get(0)?;
+            Ok(text.map(|t| fold(&t)))
+        },
+    )?;
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use anyhow::{Context, Result};
+use crate::error::Context;
+use crate::error::RequestContext;
+use crate::Result;
 use directories::BaseDirs;
 use reqwest::Client;
 use std::path::PathBuf;
@@ -32,12 +34,12 @@ pub async fn download_model() -> Result<PathBuf> {
         .get(MODEL_URL)
         .send()
         .await
-        .context("Failed to download model")?;
+        .request_context("Failed to download model")?;
 
     let bytes = response
         .bytes()
         .await
-        .context("Failed to get model bytes")?;
+        .request_context("Failed to get model bytes")?;
 
     fs::write(&model_path, bytes)
         .await
@@ -1,6 +1,6 @@
 use crate::db::models::Album;
 use crate::embed::models::AlbumVector;
-use anyhow::Result;
+use crate::Result;
 
 pub mod downloader;
 pub mod model;
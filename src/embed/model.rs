@@ -1,4 +1,4 @@
-use anyhow::Result;
+use crate::{GnedbyError, Result};
 use ndarray::Array4;
 use ort::{
     execution_providers::CPUExecutionProvider,
@@ -28,12 +28,14 @@ impl EmbeddingModel {
 
     async fn get_model_path() -> Result<PathBuf> {
         let config_dir = directories::BaseDirs::new()
-            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
+            .ok_or_else(|| GnedbyError::Storage("Could not find config directory".to_string()))?
             .config_dir()
             .join("gnedby");
         let model_path = config_dir.join("model.onnx");
         if !model_path.exists() {
-            anyhow::bail!("Model file not found. Please run 'gnedby embed load-model' first.");
+            return Err(GnedbyError::NotFound(
+                "Model file not found. Please run 'gnedby embed load-model' first.".to_string(),
+            ));
         }
         Ok(model_path)
     }
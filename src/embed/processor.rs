@@ -1,6 +1,7 @@
-use anyhow::Result;
+use crate::Result;
 use ndarray::Array4;
 
+#[derive(Default)]
 pub struct ImageProcessor;
 
 impl ImageProcessor {
@@ -0,0 +1,205 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Error returned by the gnedby library.
+///
+/// Failures from the libraries gnedby builds on (HTTP, SQLite, the embedding
+/// model and so on) are boxed, and the original error is available through
+/// [`std::error::Error::source`]. Match on [`GnedbyError::kind`] rather than
+/// on the variants to handle errors by category.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum GnedbyError {
+    /// An album, backup, model file or other item does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// A remote service could not be reached or answered with an error.
+    #[error("{0}")]
+    Network(String),
+    #[error("Request failed: {0}")]
+    Http(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// A token or passphrase was missing or rejected.
+    #[error("{0}")]
+    Auth(String),
+    /// The operation clashes with the current state, e.g. encrypting a database
+    /// that is already encrypted.
+    #[error("{0}")]
+    Conflict(String),
+    /// An argument or setting is not acceptable.
+    #[error("{0}")]
+    Validation(String),
+    /// The database or files on disk could not be read or written.
+    #[error("{0}")]
+    Storage(String),
+    #[error("Database error: {0}")]
+    Database(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Json(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Embedding model error: {0}")]
+    Model(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Failed to read image: {0}")]
+    Image(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("{0}")]
+    Prompt(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// Any other failure, with a message describing what went wrong and the
+    /// underlying error, if any.
+    #[error("{message}")]
+    Other {
+        message: String,
+        #[source]
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+}
+
+/// Broad category of a `GnedbyError`, used to pick exit codes and HTTP statuses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    Network,
+    Auth,
+    Conflict,
+    Validation,
+    Storage,
+    Other,
+}
+
+impl GnedbyError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            GnedbyError::NotFound(_) => ErrorKind::NotFound,
+            GnedbyError::Network(_) => ErrorKind::Network,
+            GnedbyError::Http(e) => match e
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status)
+            {
+                Some(status) => status_kind(status),
+                None => ErrorKind::Network,
+            },
+            GnedbyError::Auth(_) => ErrorKind::Auth,
+            GnedbyError::Conflict(_) => ErrorKind::Conflict,
+            GnedbyError::Validation(_) => ErrorKind::Validation,
+            GnedbyError::Storage(_) | GnedbyError::Io(_) => ErrorKind::Storage,
+            GnedbyError::Database(e) => match e
+                .downcast_ref::<rusqlite::Error>()
+                .and_then(rusqlite::Error::sqlite_error_code)
+            {
+                Some(rusqlite::ErrorCode::ConstraintViolation) => ErrorKind::Conflict,
+                Some(rusqlite::ErrorCode::NotADatabase) => ErrorKind::Auth,
+                _ => ErrorKind::Storage,
+            },
+            GnedbyError::Json(_)
+            | GnedbyError::Model(_)
+            | GnedbyError::Image(_)
+            | GnedbyError::Prompt(_)
+            | GnedbyError::Other { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// Turns an unsuccessful response from a remote service into an error of
+    /// the matching kind, so a 404 from the sync server reads as not-found.
+    pub(crate) fn from_status(status: StatusCode, message: String) -> Self {
+        match status_kind(status) {
+            ErrorKind::NotFound => GnedbyError::NotFound(message),
+            ErrorKind::Auth => GnedbyError::Auth(message),
+            ErrorKind::Conflict => GnedbyError::Conflict(message),
+            ErrorKind::Validation => GnedbyError::Validation(message),
+            _ => GnedbyError::Network(message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for GnedbyError {
+    fn from(error: reqwest::Error) -> Self {
+        GnedbyError::Http(Box::new(error))
+    }
+}
+
+impl From<rusqlite::Error> for GnedbyError {
+    fn from(error: rusqlite::Error) -> Self {
+        GnedbyError::Database(Box::new(error))
+    }
+}
+
+impl From<serde_json::Error> for GnedbyError {
+    fn from(error: serde_json::Error) -> Self {
+        GnedbyError::Json(Box::new(error))
+    }
+}
+
+impl From<ort::Error> for GnedbyError {
+    fn from(error: ort::Error) -> Self {
+        GnedbyError::Model(Box::new(error))
+    }
+}
+
+impl From<image::ImageError> for GnedbyError {
+    fn from(error: image::ImageError) -> Self {
+        GnedbyError::Image(Box::new(error))
+    }
+}
+
+impl From<dialoguer::Error> for GnedbyError {
+    fn from(error: dialoguer::Error) -> Self {
+        GnedbyError::Prompt(Box::new(error))
+    }
+}
+
+/// Adds a message to a failure, keeping the original error as its source.
+pub(crate) trait Context<T> {
+    fn context(self, message: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E> Context<T> for std::result::Result<T, E>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| GnedbyError::Other {
+            message: message.into(),
+            source: Some(Box::new(e)),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.ok_or_else(|| GnedbyError::Other {
+            message: message.into(),
+            source: None,
+        })
+    }
+}
+
+/// Adds a message to a failed HTTP request while keeping it classified as a
+/// network error, or by status for `error_for_status`, which `Context` would
+/// lose.
+pub(crate) trait RequestContext<T> {
+    fn request_context(self, message: &str) -> Result<T>;
+}
+
+impl<T> RequestContext<T> for std::result::Result<T, reqwest::Error> {
+    fn request_context(self, message: &str) -> Result<T> {
+        self.map_err(|e| {
+            let message = format!("{}: {}", message, e);
+            match e.status() {
+                Some(status) => GnedbyError::from_status(status, message),
+                None => GnedbyError::Network(message),
+            }
+        })
+    }
+}
+
+fn status_kind(status: StatusCode) -> ErrorKind {
+    match status {
+        StatusCode::NOT_FOUND => ErrorKind::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::Auth,
+        StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => ErrorKind::Conflict,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => ErrorKind::Validation,
+        _ => ErrorKind::Network,
+    }
+}
+
+pub type Result<T, E = GnedbyError> = std::result::Result<T, E>;
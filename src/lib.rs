@@ -0,0 +1,94 @@
+//! Library behind the `gnedby` CLI for managing a CD/LP collection.
+//!
+//! The main entry points are re-exported at the crate root:
+//!
+//! - [`Database`], the handle to an album collection, queried with
+//!   [`AlbumFilter`] and [`Page`].
+//! - [`MetadataProvider`] implementations such as [`AppleMusicProvider`],
+//!   [`MusicBrainzProvider`], [`DiscogsProvider`] and [`BandcampProvider`], and
+//!   the [`ProviderRegistry`] that picks between them.
+//! - [`sync`], for pushing and pulling the collection to remote storage.
+//! - [`Embedder`], which turns album artwork into vectors for similarity search.
+//!
+//! Everything reachable from the crate root follows semantic versioning, except
+//! the hidden `internal` module, which only exists for the CLI. All fallible
+//! functions return [`Result`] with the typed [`GnedbyError`]; errors from the
+//! libraries gnedby builds on are boxed behind its `source()`.
+//!
+//! ```no_run
+//! use gnedby::{AlbumFilter, Database, Page};
+//!
+//! # async fn run() -> gnedby::Result<()> {
+//! let db = Database::new().await?;
+//! let filter = AlbumFilter {
+//!     artist: Some("IU".to_string()),
+//!     ..Default::default()
+//! };
+//! for album in db.list_albums(&filter, None, Page::default()).await? {
+//!     println!("{} - {}", album.artist, album.album);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod api;
+mod backup;
+mod barcode;
+mod collection;
+mod config;
+mod country;
+mod db;
+mod embed;
+mod error;
+mod metadata;
+pub mod sync;
+mod web;
+
+pub use db::doctor::{Issue, Repair};
+pub use db::{
+    Album, AlbumFilter, ArtistCredit, ArtistRole, Database, FieldChange, Format, Genre, Page,
+    StatsGroup, Track, REFRESH_FIELDS,
+};
+pub use embed::Embedder;
+pub use error::{ErrorKind, GnedbyError, Result};
+pub use metadata::{
+    apple_album_id_from_url, fetch_album_metadata, AlbumMetadata, AppleMusicProvider,
+    BandcampProvider, DiscogsProvider, MetadataProvider, MusicBrainzProvider, ProviderRegistry,
+    TrackMetadata,
+};
+
+/// Building blocks of the `gnedby` command-line tool. They are public only so
+/// the binary can reach them, are not part of the library's API and may change
+/// in any release.
+#[doc(hidden)]
+pub mod internal {
+    pub use crate::api::{
+        bulk_upload_album_vectors, fetch_embedded_album_ids, update_album_vector,
+    };
+    pub use crate::backup::{
+        auto_backup, backups_dir, create_backup, list_backups, prune_backups, restore_backup,
+        BackupInfo,
+    };
+    pub use crate::barcode::normalize_barcode;
+    pub use crate::collection::{
+        add_from_barcode_images, add_metadata, apply_refresh, check_refresh, lookup_barcode,
+        lookup_input, lookup_search_result, search, AddOptions, BarcodeImageReport,
+    };
+    pub use crate::config::{
+        load_backup_config, load_embed_config, load_metadata_config, load_sync_config,
+        save_backup_config, save_embed_config, save_metadata_config, save_sync_config,
+        BackupConfig, EmbedConfig, MetadataConfig, SyncConfig,
+    };
+    pub use crate::country::{country_name, normalize_country};
+    pub use crate::db::cipher::{
+        decrypt_database, encrypt_database, key_for, new_passphrase, DB_KEY_ENV,
+    };
+    pub use crate::db::doctor::{
+        apply_file_repair, check_database, find_leftover_files, normalize_release_date,
+    };
+    pub use crate::db::{get_db_path, is_various_artists, split_genres};
+    pub use crate::embed::downloader::download_model;
+    pub use crate::embed::models::AlbumVector;
+    pub use crate::metadata::PROVIDER_NAMES;
+    pub use crate::web::serve;
+}
@@ -1,26 +1,54 @@
-mod api;
 mod cli;
-mod config;
-mod db;
-mod embed;
-mod metadata;
-mod sync;
-mod web;
 
 use anyhow::Result;
-use api::{bulk_upload_album_vectors, fetch_embedded_album_ids, update_album_vector};
-use cli::{parse_args, Command, EmbedCommand, EmbedConfigCommand, SyncCommand, SyncConfigCommand};
+use cli::{
+    page_output, parse_args, BackupCommand, BackupConfigCommand, Command, DbCommand, EmbedCommand,
+    EmbedConfigCommand, GenreCommand, ProviderCommand, ProviderConfigCommand, SyncCommand,
+    SyncConfigCommand,
+};
 use comfy_table::{presets::UTF8_BORDERS_ONLY, Cell, CellAlignment, ContentArrangement, Table};
-use config::{load_embed_config, load_sync_config, save_embed_config, save_sync_config};
-use db::{Album, Database};
-use dialoguer::Input;
-use embed::{models::AlbumVector, Embedder};
-use metadata::fetch_album_metadata;
+use dialoguer::{Confirm, Input, Select};
+use gnedby::internal::{
+    add_from_barcode_images, add_metadata, apply_file_repair, apply_refresh, auto_backup,
+    backups_dir, bulk_upload_album_vectors, check_database, check_refresh, country_name,
+    create_backup, decrypt_database, download_model, encrypt_database, fetch_embedded_album_ids,
+    find_leftover_files, get_db_path, is_various_artists, key_for, list_backups,
+    load_backup_config, load_embed_config, load_metadata_config, load_sync_config, lookup_barcode,
+    lookup_input, lookup_search_result, new_passphrase, normalize_barcode, normalize_country,
+    normalize_release_date, prune_backups, restore_backup, save_backup_config, save_embed_config,
+    save_metadata_config, save_sync_config, search, serve, split_genres, update_album_vector,
+    AddOptions, AlbumVector, BackupConfig, BackupInfo, BarcodeImageReport, EmbedConfig,
+    MetadataConfig, SyncConfig, DB_KEY_ENV, PROVIDER_NAMES,
+};
+use gnedby::{
+    sync, Album, AlbumFilter, AlbumMetadata, ArtistCredit, ArtistRole, Database, Embedder,
+    ErrorKind, Format, GnedbyError, Issue, MetadataProvider, Page, ProviderRegistry, Repair,
+    StatsGroup, Track,
+};
+use std::io::IsTerminal;
+use std::path::Path;
 
 fn main() {
     if let Err(e) = run() {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(exit_code(&e));
+    }
+}
+
+/// Exit status for a failed command, so scripts can tell failures apart.
+/// 2 is left to clap for usage errors.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    let Some(error) = error.downcast_ref::<GnedbyError>() else {
+        return 1;
+    };
+    match error.kind() {
+        ErrorKind::NotFound => 3,
+        ErrorKind::Network => 4,
+        ErrorKind::Auth => 5,
+        ErrorKind::Conflict => 6,
+        ErrorKind::Validation => 7,
+        ErrorKind::Storage => 8,
+        _ => 1,
     }
 }
 
@@ -90,30 +118,408 @@ fn create_artist_table(stats: Vec<(String, i64)>) -> Result<()> {
     Ok(())
 }
 
+/// Prints one page of albums under `heading`, through the pager when the
+/// output goes to a terminal.
+fn print_album_table(
+    heading: &str,
+    albums: &[Album],
+    page: Page,
+    total: i64,
+    no_pager: bool,
+) -> Result<()> {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("ID").set_alignment(CellAlignment::Center),
+            Cell::new("Album").set_alignment(CellAlignment::Center),
+            Cell::new("Artist").set_alignment(CellAlignment::Center),
+            Cell::new("Genre").set_alignment(CellAlignment::Center),
+            Cell::new("Country").set_alignment(CellAlignment::Center),
+            Cell::new("Format").set_alignment(CellAlignment::Center),
+            Cell::new("Year").set_alignment(CellAlignment::Center),
+        ]);
+
+    for album in albums {
+        let year = extract_year(&album.release_date);
+
+        table.add_row(vec![
+            Cell::new(album.id.unwrap_or(0).to_string()),
+            Cell::new(&album.album),
+            Cell::new(&album.artist),
+            Cell::new(&album.genre),
+            Cell::new(album.origin_country.as_deref().map_or("-", country_name)),
+            Cell::new(album.format.as_str()),
+            Cell::new(year),
+        ]);
+    }
+
+    let summary = if albums.len() as i64 == total {
+        format!("{} album(s) found", total)
+    } else {
+        format!(
+            "Showing {}-{} of {} album(s)",
+            page.offset as usize + 1,
+            page.offset as usize + albums.len(),
+            total
+        )
+    };
+    page_output(&format!("{heading}\n{table}\n{summary}\n"), no_pager)
+}
+
+fn print_backup_table(backups: &[BackupInfo]) {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("Name").set_alignment(CellAlignment::Center),
+            Cell::new("Created").set_alignment(CellAlignment::Center),
+            Cell::new("Before").set_alignment(CellAlignment::Center),
+            Cell::new("Size").set_alignment(CellAlignment::Center),
+        ]);
+
+    for backup in backups {
+        table.add_row(vec![
+            Cell::new(&backup.name),
+            Cell::new(
+                backup
+                    .created
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S"),
+            ),
+            Cell::new(backup.reason.as_deref().unwrap_or("-")),
+            Cell::new(format!("{} KB", backup.size.div_ceil(1024)))
+                .set_alignment(CellAlignment::Right),
+        ]);
+    }
+
+    println!("{table}");
+    println!("{} backup(s)", backups.len());
+}
+
 #[tokio::main]
 async fn run() -> Result<()> {
     let cli = parse_args()?;
+
+    // These commands work on the database file itself, so they run before it is
+    // opened and migrated.
+    match &cli.command {
+        Command::Doctor { fix } => {
+            let db_path = get_db_path()?;
+            let mut issues = check_database(&db_path)?;
+            issues.extend(find_leftover_files(&db_path));
+
+            if issues.is_empty() {
+                println!("No problems found.");
+                return Ok(());
+            }
+
+            println!("Found {} problem(s):", issues.len());
+            for issue in &issues {
+                println!("  - {}", issue);
+            }
+
+            if !fix && !std::io::stdin().is_terminal() {
+                println!("\nRun 'gnedby doctor --fix' to repair them.");
+                return Ok(());
+            }
+
+            auto_backup("doctor")?;
+            // Repairs to the database itself need it migrated, so it is only
+            // opened once such a repair is chosen.
+            let mut db = None;
+            let mut fixed = 0;
+            for issue in &issues {
+                let repair = if *fix {
+                    issue.automatic_repair()
+                } else {
+                    println!("\n{}", issue);
+                    prompt_repair(issue)?
+                };
+
+                match repair {
+                    Some(repair) if repair.needs_database() => {
+                        if db.is_none() {
+                            db = Some(Database::new().await?);
+                        }
+                        if let Some(db) = &db {
+                            db.apply_repair(&repair).await?;
+                        }
+                        fixed += 1;
+                    }
+                    Some(repair) => {
+                        apply_file_repair(&repair)?;
+                        fixed += 1;
+                    }
+                    None if *fix => println!("Could not repair automatically: {}", issue),
+                    None => {}
+                }
+            }
+            println!("\nRepaired {} of {} problem(s).", fixed, issues.len());
+            if issues
+                .iter()
+                .any(|issue| matches!(issue, Issue::Integrity(_)))
+            {
+                println!(
+                    "The database file is damaged and needs a manual restore. Pick a backup with 'gnedby backup list' and run 'gnedby backup restore <name>'."
+                );
+            }
+            return Ok(());
+        }
+        Command::Db { command } => {
+            let db_path = get_db_path()?;
+            match command {
+                DbCommand::Encrypt => {
+                    let passphrase = new_passphrase()?;
+                    encrypt_database(&db_path, &passphrase)?;
+                    println!(
+                        "Database encrypted. Keep your passphrase safe: it cannot be recovered."
+                    );
+                    println!("Set {} to unlock it without a prompt.", DB_KEY_ENV);
+
+                    let backups = list_backups()?;
+                    if !backups.is_empty() {
+                        println!(
+                            "Note: {} existing backup(s) in {} are not encrypted.",
+                            backups.len(),
+                            backups_dir()?.display()
+                        );
+                    }
+                }
+                DbCommand::Decrypt => {
+                    let passphrase = key_for(&db_path)?.ok_or_else(|| {
+                        GnedbyError::Conflict("The database is not encrypted".to_string())
+                    })?;
+                    auto_backup("decrypt")?;
+                    decrypt_database(&db_path, &passphrase)?;
+                    println!("Database decrypted.");
+                }
+            }
+            return Ok(());
+        }
+        Command::Backup { command } => {
+            match command {
+                BackupCommand::Create => match create_backup(None)? {
+                    Some(backup) => println!("Created backup {}", backup.name),
+                    None => println!("There is no database to back up yet."),
+                },
+                BackupCommand::List => {
+                    let backups = list_backups()?;
+                    if backups.is_empty() {
+                        println!("No backups found.");
+                    } else {
+                        print_backup_table(&backups);
+                    }
+                }
+                BackupCommand::Restore { name } => {
+                    let safety_backup = restore_backup(name)?;
+                    println!("Restored backup {}", name);
+                    if let Some(backup) = safety_backup {
+                        println!(
+                            "The previous database was saved as {}. Restore it to undo.",
+                            backup.name
+                        );
+                    }
+                }
+                BackupCommand::Prune => {
+                    let removed = prune_backups(&load_backup_config()?)?;
+                    for backup in &removed {
+                        println!("Removed backup {}", backup.name);
+                    }
+                    println!("{} backup(s) removed", removed.len());
+                }
+                BackupCommand::Config { command } => match command {
+                    BackupConfigCommand::Show => {
+                        let config = load_backup_config()?;
+                        println!("Current backup configuration:");
+                        println!("{}", serde_json::to_string_pretty(&config)?);
+                    }
+                    BackupConfigCommand::Set { key, value } => {
+                        let mut config = load_backup_config()?;
+
+                        match key.as_str() {
+                            "auto_backup" => {
+                                config.auto_backup = value.to_lowercase() == "true";
+                                println!("Set auto_backup to: {}", config.auto_backup);
+                            }
+                            "keep" => {
+                                config.keep = value.parse().map_err(|_| {
+                                    GnedbyError::Validation("keep must be a number".to_string())
+                                })?;
+                                println!("Set keep to: {}", config.keep);
+                            }
+                            "max_age_days" => {
+                                config.max_age_days = match value.as_str() {
+                                    "" | "none" => None,
+                                    days => Some(days.parse().map_err(|_| {
+                                        GnedbyError::Validation(
+                                            "max_age_days must be a number or none".to_string(),
+                                        )
+                                    })?),
+                                };
+                                println!("Set max_age_days to: {:?}", config.max_age_days);
+                            }
+                            _ => {
+                                println!("Unknown backup configuration key: {}", key);
+                            }
+                        }
+
+                        save_backup_config(&config)?;
+                    }
+                    BackupConfigCommand::Reset => {
+                        save_backup_config(&BackupConfig::default())?;
+                        println!("Backup configuration has been reset to default values.");
+                    }
+                },
+            }
+            return Ok(());
+        }
+        Command::Provider { command } => {
+            match command {
+                ProviderCommand::List => {
+                    let config = load_metadata_config()?;
+                    for name in PROVIDER_NAMES {
+                        let enabled = config.providers.iter().any(|p| p == name);
+                        let default = config.default_provider == *name;
+                        println!(
+                            "{}{}{}",
+                            name,
+                            if enabled { "" } else { " (disabled)" },
+                            if default { " (default)" } else { "" }
+                        );
+                    }
+                }
+                ProviderCommand::Config { command } => match command {
+                    ProviderConfigCommand::Show => {
+                        let config = load_metadata_config()?;
+                        println!("Current provider configuration:");
+                        println!("{}", serde_json::to_string_pretty(&config)?);
+                    }
+                    ProviderConfigCommand::Set { key, value } => {
+                        let mut config = load_metadata_config()?;
+
+                        match key.as_str() {
+                            "default_provider" => {
+                                config.default_provider = value.trim().to_lowercase();
+                                println!("Set default_provider to: {}", config.default_provider);
+                            }
+                            "providers" => {
+                                config.providers = value
+                                    .split(',')
+                                    .map(|name| name.trim().to_lowercase())
+                                    .filter(|name| !name.is_empty())
+                                    .collect();
+                                println!("Set providers to: {}", config.providers.join(", "));
+                            }
+                            "apple_store" => {
+                                let country = normalize_country(value).ok_or_else(|| {
+                                    GnedbyError::Validation(format!("Unknown country: {}", value))
+                                })?;
+                                config.apple_store = Some(country.alpha2.to_lowercase());
+                                println!("Set apple_store to: {}", country.alpha2.to_lowercase());
+                            }
+                            "apple_lang" => {
+                                let lang = value.trim().to_lowercase().replace('-', "_");
+                                println!("Set apple_lang to: {}", lang);
+                                config.apple_lang = Some(lang);
+                            }
+                            "artwork_size" => {
+                                config.artwork_size = match value.parse() {
+                                    Ok(size) if (100..=3000).contains(&size) => size,
+                                    _ => {
+                                        return Err(GnedbyError::Validation(format!(
+                                            "artwork_size must be between 100 and 3000: {}",
+                                            value
+                                        ))
+                                        .into())
+                                    }
+                                };
+                                println!("Set artwork_size to: {}", config.artwork_size);
+                            }
+                            "discogs_token" => {
+                                config.discogs_token = Some(value.clone());
+                                println!("Set discogs_token to: {}", value);
+                            }
+                            _ => {
+                                println!("Unknown provider configuration key: {}", key);
+                            }
+                        }
+
+                        // Fails on unknown or disabled providers before saving.
+                        ProviderRegistry::from_config(&config)?.default_provider()?;
+                        save_metadata_config(&config)?;
+
+                        if key == "artwork_size" {
+                            auto_backup("artwork-size")?;
+                            let resized = Database::new()
+                                .await?
+                                .resize_artwork(config.artwork_size)
+                                .await?;
+                            println!("Updated the artwork of {} album(s)", resized);
+                        }
+                    }
+                    ProviderConfigCommand::Reset => {
+                        save_metadata_config(&MetadataConfig::default())?;
+                        println!("Provider configuration has been reset to default values.");
+                    }
+                },
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
     let db = Database::new().await?;
 
     match cli.command {
-        Command::Add { album_ids, format } => {
-            for album_id in album_ids {
-                let metadata = fetch_album_metadata(&album_id).await?;
-
-                let album = Album {
-                    id: None,
-                    artist: metadata.artist,
-                    album: metadata.album,
-                    genre: metadata.genre,
-                    release_date: metadata.release_date,
-                    format,
-                    source_url: metadata.source_url,
-                    country: metadata.country,
-                    artwork_url: metadata.artwork_url,
+        Command::Add {
+            album_ids,
+            search,
+            upc,
+            barcode_image,
+            provider,
+            store,
+            format,
+            origin,
+        } => {
+            let mut config = load_metadata_config()?;
+            if store.is_some() {
+                config.apple_store = store;
+            }
+            let registry = ProviderRegistry::from_config(&config)?;
+            let provider = provider.map(|name| registry.get(&name)).transpose()?;
+
+            let options = AddOptions { format, origin };
+
+            if let Some(query) = search {
+                let provider = match &provider {
+                    Some(provider) => provider.clone(),
+                    None => registry.default_provider()?,
                 };
+                match choose_search_result(provider.as_ref(), &query).await? {
+                    Some(metadata) => print_added(&add_metadata(&db, metadata, &options).await?),
+                    None => println!("No album selected"),
+                }
+            }
 
-                db.add_album(&album).await?;
-                println!("Added album \"{}\" by \"{}\"", album.album, album.artist);
+            for code in upc {
+                let barcode = normalize_barcode(&code)?;
+                let metadata = lookup_barcode(&registry, provider.as_ref(), &barcode).await?;
+                print_added(&add_metadata(&db, metadata, &options).await?);
+            }
+
+            if let Some(path) = barcode_image {
+                let report =
+                    add_from_barcode_images(&db, &registry, provider.as_ref(), &path, &options)
+                        .await?;
+                print_barcode_image_report(&path, &report);
+            }
+
+            for album_id in album_ids {
+                let metadata = lookup_input(&registry, provider.as_ref(), &album_id).await?;
+                print_added(&add_metadata(&db, metadata, &options).await?);
             }
 
             let config = load_sync_config()?;
@@ -126,7 +532,7 @@ async fn run() -> Result<()> {
         }
         Command::ManualAdd { format } => {
             let artist: String = Input::<String>::new()
-                .with_prompt("Enter artist name")
+                .with_prompt("Enter artist name (join multiple artists with 'with' or 'feat.')")
                 .allow_empty(false)
                 .interact_text()?;
 
@@ -136,7 +542,7 @@ async fn run() -> Result<()> {
                 .interact_text()?;
 
             let genre: String = Input::<String>::new()
-                .with_prompt("Enter genre")
+                .with_prompt("Enter genre (separate multiple genres with ',')")
                 .allow_empty(false)
                 .interact_text()?;
 
@@ -145,10 +551,16 @@ async fn run() -> Result<()> {
                 .allow_empty(false)
                 .interact_text()?;
 
-            let country: String = Input::<String>::new()
-                .with_prompt("Enter country")
+            let origin: String = Input::<String>::new()
+                .with_prompt("Enter country of origin (e.g., KR, Korea, Japan)")
                 .allow_empty(false)
+                .validate_with(|input: &String| -> Result<(), String> {
+                    normalize_country(input)
+                        .map(|_| ())
+                        .ok_or_else(|| format!("Unknown country: {}", input))
+                })
                 .interact_text()?;
+            let origin_country = normalize_country(&origin).map(|c| c.alpha2.to_string());
 
             let source_url: String = Input::<String>::new()
                 .with_prompt("Enter source URL")
@@ -160,16 +572,29 @@ async fn run() -> Result<()> {
                 .allow_empty(false)
                 .interact_text()?;
 
+            let artists = ArtistCredit::parse(&artist);
+            let compilation = is_various_artists(&artist);
             let album = Album {
                 id: None,
                 artist,
+                artists,
+                compilation,
                 album,
                 genre,
                 release_date,
                 format,
                 source_url,
-                country,
+                storefront: None,
+                origin_country,
                 artwork_url,
+                provider: None,
+                source_id: None,
+                label: None,
+                catalog_number: None,
+                barcode: None,
+                format_details: None,
+                genres: Vec::new(),
+                edited_fields: Vec::new(),
             };
 
             db.add_album(&album).await?;
@@ -183,22 +608,21 @@ async fn run() -> Result<()> {
                 }
             }
         }
-        Command::Delete { id } => match db.delete_album(id).await {
-            Ok(_) => {
-                println!("Album with ID {} deleted successfully", id);
-
-                let config = load_sync_config()?;
-                if config.auto_sync && config.storage_url.is_some() && config.token.is_some() {
-                    match sync::auto_sync().await {
-                        Ok(_) => println!("Auto sync completed successfully"),
-                        Err(e) => eprintln!("Auto sync failed: {}", e),
-                    }
-                }
+        Command::Delete { id } => {
+            if db.get_album_by_id(id).await?.is_some() {
+                auto_backup("delete")?;
             }
-            Err(e) => {
-                eprintln!("Failed to delete album: {}", e);
+            db.delete_album(id).await?;
+            println!("Album with ID {} deleted successfully", id);
+
+            let config = load_sync_config()?;
+            if config.auto_sync && config.storage_url.is_some() && config.token.is_some() {
+                match sync::auto_sync().await {
+                    Ok(_) => println!("Auto sync completed successfully"),
+                    Err(e) => eprintln!("Auto sync failed: {}", e),
+                }
             }
-        },
+        }
         Command::Show {
             year,
             artist,
@@ -206,6 +630,9 @@ async fn run() -> Result<()> {
             format,
             country,
             order_by,
+            limit,
+            offset,
+            no_pager,
         } => {
             let filter_msg = if let Some(year) = year {
                 format!("by year: {}", year)
@@ -216,88 +643,315 @@ async fn run() -> Result<()> {
             } else if let Some(format) = &format {
                 format!("by format: {}", format)
             } else if let Some(country) = &country {
-                format!("by country: {}", country)
+                format!("by country: {}", country_name(country))
             } else {
                 "all albums".to_string()
             };
 
-            let artist_ref = artist.as_deref();
-            let genre_ref = genre.as_deref();
-            let format_ref = format.as_ref().map(|f| f.as_str());
-            let country_ref = country.as_deref();
-            let order_by_ref = order_by.as_deref();
-
-            let albums = db
-                .list_albums(
-                    year,
-                    artist_ref,
-                    genre_ref,
-                    format_ref,
-                    country_ref,
-                    order_by_ref,
-                )
-                .await?;
+            let filter = AlbumFilter {
+                year,
+                artist,
+                genre,
+                format,
+                country,
+                query: None,
+            };
+            let page = Page {
+                limit,
+                offset,
+                after: None,
+            };
+            let albums = db.list_albums(&filter, order_by.as_deref(), page).await?;
 
             if albums.is_empty() {
                 println!("No albums found in my GNEDBY {}", filter_msg);
                 return Ok(());
             }
 
+            let total = db.count_albums(&filter).await?;
+            let heading = format!("In my GNEDBY, {}", filter_msg);
+            print_album_table(&heading, &albums, page, total, no_pager)?;
+        }
+        Command::Search {
+            query,
+            order_by,
+            limit,
+            offset,
+            no_pager,
+        } => {
+            let filter = AlbumFilter {
+                query: Some(query.clone()),
+                ..Default::default()
+            };
+            let page = Page {
+                limit,
+                offset,
+                after: None,
+            };
+            let albums = db.list_albums(&filter, order_by.as_deref(), page).await?;
+
+            if albums.is_empty() {
+                println!("No albums matching \"{}\" found in my GNEDBY", query);
+                return Ok(());
+            }
+
+            let total = db.count_albums(&filter).await?;
+            let heading = format!("In my GNEDBY, albums matching \"{}\"", query);
+            print_album_table(&heading, &albums, page, total, no_pager)?;
+        }
+        Command::Report {
+            year: _,
+            artist,
+            genre,
+            top_level,
+            format,
+            country,
+        } => {
+            if artist {
+                let artist_stats = db.get_stats(StatsGroup::Artist).await?;
+                create_artist_table(artist_stats)?;
+            } else if genre {
+                let genre_stats = db.get_stats(StatsGroup::Genre { top_level }).await?;
+                let title = if top_level {
+                    "Albums by Top-Level Genre"
+                } else {
+                    "Albums by Genre"
+                };
+                create_bar_chart_table(genre_stats, title, "Genre")?;
+            } else if format {
+                let format_stats = db.get_stats(StatsGroup::Format).await?;
+                create_bar_chart_table(format_stats, "Albums by Format", "Format")?;
+            } else if country {
+                let country_stats = db.get_stats(StatsGroup::Country).await?;
+                create_bar_chart_table(country_stats, "Albums by Country", "Country")?;
+            } else {
+                let year_stats = db.get_stats(StatsGroup::Year).await?;
+                create_bar_chart_table(year_stats, "Albums by Year", "Year")?;
+            }
+        }
+        Command::Tracks { id, refresh } => {
+            let album = db
+                .get_album_by_id(id)
+                .await?
+                .ok_or_else(|| GnedbyError::NotFound(format!("Album with ID {} not found", id)))?;
+
+            let mut tracks = db.get_tracks(id).await?;
+            if tracks.is_empty() || refresh {
+                let registry = ProviderRegistry::from_config(&load_metadata_config()?)?;
+                if let Some(metadata) = registry.lookup_album(&album).await? {
+                    tracks = metadata.tracks.into_iter().map(Track::from).collect();
+                    db.replace_tracks(id, &tracks).await?;
+                }
+            }
+
+            if tracks.is_empty() {
+                println!(
+                    "No track listing available for \"{}\" by \"{}\"",
+                    album.album, album.artist
+                );
+                return Ok(());
+            }
+
+            let multi_disc = tracks.iter().any(|t| t.disc_number > 1);
+
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_BORDERS_ONLY)
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_header(vec![
+                    Cell::new("#").set_alignment(CellAlignment::Center),
+                    Cell::new("Title").set_alignment(CellAlignment::Center),
+                    Cell::new("Time").set_alignment(CellAlignment::Center),
+                ]);
+
+            for track in &tracks {
+                let number = if multi_disc {
+                    format!("{}-{}", track.disc_number, track.track_number)
+                } else {
+                    track.track_number.to_string()
+                };
+                table.add_row(vec![
+                    Cell::new(number),
+                    Cell::new(&track.title),
+                    Cell::new(track.duration_display()),
+                ]);
+            }
+
+            println!("\"{}\" by \"{}\"", album.album, album.artist);
+            println!("{table}");
+            println!("{} track(s)", tracks.len());
+        }
+        Command::Has { title } => {
+            let matches = db.find_tracks(&title).await?;
+
+            if matches.is_empty() {
+                println!("No songs matching \"{}\" found in my GNEDBY", title);
+                return Ok(());
+            }
+
             let mut table = Table::new();
             table
                 .load_preset(UTF8_BORDERS_ONLY)
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_header(vec![
                     Cell::new("ID").set_alignment(CellAlignment::Center),
+                    Cell::new("Song").set_alignment(CellAlignment::Center),
                     Cell::new("Album").set_alignment(CellAlignment::Center),
                     Cell::new("Artist").set_alignment(CellAlignment::Center),
-                    Cell::new("Genre").set_alignment(CellAlignment::Center),
-                    Cell::new("Country").set_alignment(CellAlignment::Center),
                     Cell::new("Format").set_alignment(CellAlignment::Center),
-                    Cell::new("Year").set_alignment(CellAlignment::Center),
+                    Cell::new("Track").set_alignment(CellAlignment::Center),
                 ]);
 
-            for album in &albums {
-                let year = extract_year(&album.release_date);
-
+            for (album, track) in &matches {
                 table.add_row(vec![
                     Cell::new(album.id.unwrap_or(0).to_string()),
+                    Cell::new(&track.title),
                     Cell::new(&album.album),
                     Cell::new(&album.artist),
-                    Cell::new(&album.genre),
-                    Cell::new(&album.country),
                     Cell::new(album.format.as_str()),
-                    Cell::new(year),
+                    Cell::new(format!("{}-{}", track.disc_number, track.track_number)),
                 ]);
             }
 
-            println!("In my GNEDBY, {}", filter_msg);
+            println!("In my GNEDBY, songs matching \"{}\"", title);
             println!("{table}");
-            println!("{} album(s) found", albums.len());
+            println!("{} song(s) found", matches.len());
         }
-        Command::Report {
-            year: _,
-            artist,
-            genre,
-            format,
-            country,
-        } => {
-            if artist {
-                let artist_stats = db.get_artist_stats().await?;
-                create_artist_table(artist_stats)?;
-            } else if genre {
-                let genre_stats = db.get_genre_stats().await?;
-                create_bar_chart_table(genre_stats, "Albums by Genre", "Genre")?;
-            } else if format {
-                let format_stats = db.get_format_stats().await?;
-                create_bar_chart_table(format_stats, "Albums by Format", "Format")?;
-            } else if country {
-                let country_stats = db.get_country_stats().await?;
-                create_bar_chart_table(country_stats, "Albums by Country", "Country")?;
+        Command::Genre { command } => match command {
+            GenreCommand::List => {
+                let genres = db.list_genres().await?;
+
+                let mut table = Table::new();
+                table
+                    .load_preset(UTF8_BORDERS_ONLY)
+                    .set_content_arrangement(ContentArrangement::Dynamic)
+                    .set_header(vec![
+                        Cell::new("Genre").set_alignment(CellAlignment::Left),
+                        Cell::new("Parent").set_alignment(CellAlignment::Left),
+                        Cell::new("Albums").set_alignment(CellAlignment::Left),
+                    ]);
+
+                for genre in &genres {
+                    table.add_row(vec![
+                        Cell::new(&genre.name),
+                        Cell::new(genre.parent.as_deref().unwrap_or("-")),
+                        Cell::new(genre.album_count.to_string()),
+                    ]);
+                }
+
+                println!("{table}");
+            }
+            GenreCommand::Alias { alias, genre } => {
+                auto_backup("genre-alias")?;
+                db.alias_genre(&alias, &genre).await?;
+                println!("Mapped \"{}\" to genre \"{}\"", alias, genre);
+            }
+            GenreCommand::Parent { genre, parent } => {
+                db.set_genre_parent(&genre, parent.as_deref()).await?;
+                match parent {
+                    Some(parent) => println!("Nested \"{}\" under \"{}\"", genre, parent),
+                    None => println!("\"{}\" is now a top-level genre", genre),
+                }
+            }
+            GenreCommand::Set { id, genres } => {
+                let genres: Vec<String> = genres.iter().flat_map(|g| split_genres(g)).collect();
+                db.update_album_genres(id, &genres).await?;
+                println!("Set genres of album {} to: {}", id, genres.join(", "));
+            }
+        },
+        Command::Refresh { ids, all, yes } => {
+            let albums = if all {
+                db.get_all_albums().await?
             } else {
-                let year_stats = db.get_year_stats().await?;
-                create_bar_chart_table(year_stats, "Albums by Year", "Year")?;
+                let mut albums = Vec::new();
+                for id in ids {
+                    albums.push(db.get_album_by_id(id).await?.ok_or_else(|| {
+                        GnedbyError::NotFound(format!("Album with ID {} not found", id))
+                    })?);
+                }
+                albums
+            };
+            let registry = ProviderRegistry::from_config(&load_metadata_config()?)?;
+            let interactive = std::io::stdin().is_terminal();
+            let mut backed_up = false;
+            let mut changed = 0;
+            let mut updated = 0;
+
+            for album in albums {
+                let id = album.id.unwrap_or_default();
+                let refresh = match check_refresh(&db, &registry, &album).await {
+                    Ok(Some(refresh)) => refresh,
+                    Ok(None) => {
+                        if !all {
+                            println!("Album {} was not added from a provider; skipping", id);
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("Could not refresh album {}: {}", id, e);
+                        continue;
+                    }
+                };
+
+                if refresh.is_empty() {
+                    continue;
+                }
+
+                changed += 1;
+                println!("\n{}: \"{}\" by \"{}\"", id, album.album, album.artist);
+                for change in &refresh.changes {
+                    println!(
+                        "  {}: {} -> {}",
+                        change.field,
+                        display_value(&change.old),
+                        display_value(&change.new)
+                    );
+                }
+                if refresh.tracks_changed() {
+                    println!(
+                        "  tracks: {} -> {} track(s), listing changed",
+                        refresh.stored_tracks.len(),
+                        refresh.fresh_tracks.len()
+                    );
+                }
+                if !yes && !interactive {
+                    continue;
+                }
+
+                let mut accepted = Vec::new();
+                for change in &refresh.changes {
+                    if yes || confirm(&format!("Update {}?", change.field))? {
+                        accepted.push(change);
+                    }
+                }
+                let replace_tracks =
+                    refresh.tracks_changed() && (yes || confirm("Replace the track listing?")?);
+                if accepted.is_empty() && !replace_tracks {
+                    continue;
+                }
+
+                if !backed_up {
+                    auto_backup("refresh")?;
+                    backed_up = true;
+                }
+                if apply_refresh(&db, &refresh, &accepted, replace_tracks).await? {
+                    updated += 1;
+                }
             }
+
+            if changed == 0 {
+                println!("All albums are up to date.");
+            } else if !yes && !interactive {
+                println!("\nRun 'gnedby refresh --yes' to apply the changes.");
+            } else {
+                println!("\nUpdated {} album(s).", updated);
+            }
+        }
+        Command::Db { .. }
+        | Command::Backup { .. }
+        | Command::Provider { .. }
+        | Command::Doctor { .. } => {
+            unreachable!("handled before the database is opened")
         }
         Command::Sync { command } => match command {
             SyncCommand::Check { verbose } => {
@@ -354,7 +1008,7 @@ async fn run() -> Result<()> {
                     save_sync_config(&config)?;
                 }
                 SyncConfigCommand::Reset => {
-                    let default_config = config::SyncConfig::default();
+                    let default_config = SyncConfig::default();
                     save_sync_config(&default_config)?;
 
                     println!("Sync configuration has been reset to default values.");
@@ -363,7 +1017,7 @@ async fn run() -> Result<()> {
             },
         },
         Command::Serve => {
-            web::serve().await?;
+            serve().await?;
         }
         Command::Embed { command } => match command {
             EmbedCommand::Run { force } => {
@@ -372,10 +1026,10 @@ async fn run() -> Result<()> {
                 let config = load_embed_config()?;
                 let api_url = config
                     .api_url
-                    .ok_or_else(|| anyhow::anyhow!("api_url is not set."))?;
+                    .ok_or_else(|| GnedbyError::Validation("api_url is not set.".to_string()))?;
                 let token = config
                     .token
-                    .ok_or_else(|| anyhow::anyhow!("token is not set."))?;
+                    .ok_or_else(|| GnedbyError::Auth("token is not set.".to_string()))?;
 
                 let albums = db.get_all_albums().await?;
                 println!("Found {} albums in local database", albums.len());
@@ -419,7 +1073,7 @@ async fn run() -> Result<()> {
                 println!("Embedding generation & upload completed successfully");
             }
             EmbedCommand::LoadModel => {
-                let path = embed::downloader::download_model().await?;
+                let path = download_model().await?;
                 println!("Model downloaded successfully to: {}", path.display());
             }
             EmbedCommand::Config { command } => match command {
@@ -446,7 +1100,7 @@ async fn run() -> Result<()> {
                     save_embed_config(&config)?;
                 }
                 EmbedConfigCommand::Reset => {
-                    let default_config = config::EmbedConfig::default();
+                    let default_config = EmbedConfig::default();
                     save_embed_config(&default_config)?;
 
                     println!("Embedding configuration has been reset to default values.");
@@ -458,6 +1112,173 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
+fn print_added(album: &Album) {
+    println!("Added album \"{}\" by \"{}\"", album.album, album.artist);
+}
+
+fn print_barcode_image_report(path: &Path, report: &BarcodeImageReport) {
+    if report.images == 0 {
+        println!("No images found in {}", path.display());
+        return;
+    }
+
+    println!(
+        "Matched {} of {} image(s)",
+        report.added.len(),
+        report.images
+    );
+    if !report.misses.is_empty() {
+        println!("Not added:");
+        for (file, reason) in &report.misses {
+            println!("  {}: {}", file.display(), reason);
+        }
+    }
+}
+
+/// Searches `provider` and lets the user pick one of the results, returning
+/// it with its tracks, or `None` when the selection is cancelled.
+async fn choose_search_result(
+    provider: &dyn MetadataProvider,
+    query: &str,
+) -> Result<Option<AlbumMetadata>> {
+    let results = search(provider, query).await?;
+
+    let items: Vec<String> = results
+        .iter()
+        .map(|result| {
+            let mut details = vec![extract_year(&result.release_date).to_string()];
+            let track_count = result
+                .track_count
+                .or_else(|| Some(result.tracks.len() as i64).filter(|count| *count > 0));
+            if let Some(count) = track_count {
+                details.push(format!("{} tracks", count));
+            }
+            if let Some(storefront) = &result.storefront {
+                details.push(storefront.clone());
+            }
+            details.retain(|detail| !detail.is_empty());
+            format!(
+                "{} - {} ({})",
+                result.artist,
+                result.album,
+                details.join(", ")
+            )
+        })
+        .collect();
+
+    let Some(choice) = Select::new()
+        .with_prompt("Select an album to add (Esc to cancel)")
+        .items(&items)
+        .default(0)
+        .interact_opt()?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        lookup_search_result(provider, &results[choice]).await?,
+    ))
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    Ok(Confirm::new()
+        .with_prompt(prompt)
+        .default(true)
+        .interact()?)
+}
+
+fn display_value(value: &str) -> &str {
+    if value.is_empty() {
+        "(none)"
+    } else {
+        value
+    }
+}
+
+/// Asks how to repair `issue`, returning `None` when the user skips it.
+fn prompt_repair(issue: &Issue) -> Result<Option<Repair>> {
+    let repair = match issue {
+        Issue::InvalidFormat { id, .. } => {
+            let formats = [Format::Cd, Format::Lp, Format::Usb, Format::Tape];
+            let mut items: Vec<&str> = formats.iter().map(|f| f.as_str()).collect();
+            items.push("skip");
+            let choice = Select::new()
+                .with_prompt("Set the format to")
+                .items(&items)
+                .default(0)
+                .interact()?;
+            formats.get(choice).map(|format| Repair::SetFormat {
+                id: *id,
+                format: *format,
+            })
+        }
+        Issue::InvalidRole {
+            album_id, position, ..
+        } => {
+            let roles = [
+                ArtistRole::Primary,
+                ArtistRole::Featuring,
+                ArtistRole::Various,
+            ];
+            let mut items: Vec<&str> = roles.iter().map(|r| r.as_str()).collect();
+            items.push("skip");
+            let choice = Select::new()
+                .with_prompt("Set the role to")
+                .items(&items)
+                .default(0)
+                .interact()?;
+            roles.get(choice).map(|role| Repair::SetRole {
+                album_id: *album_id,
+                position: *position,
+                role: *role,
+            })
+        }
+        Issue::MissingField { id, column } if matches!(*column, "artist" | "album") => {
+            let value: String = Input::<String>::new()
+                .with_prompt(format!("Enter the {} (leave empty to skip)", column))
+                .allow_empty(true)
+                .interact_text()?;
+            (!value.trim().is_empty()).then(|| Repair::SetText {
+                id: *id,
+                column,
+                value: value.trim().to_string(),
+            })
+        }
+        Issue::Integrity(_) => None,
+        Issue::InvalidDate { id, value } => {
+            let date: String = Input::<String>::new()
+                .with_prompt("Enter the release date (YYYY-MM-DD, leave empty to skip)")
+                .with_initial_text(normalize_release_date(value).unwrap_or_default())
+                .allow_empty(true)
+                .validate_with(|input: &String| -> Result<(), String> {
+                    if input.is_empty() || normalize_release_date(input).is_some() {
+                        Ok(())
+                    } else {
+                        Err("Use the YYYY-MM-DD format".to_string())
+                    }
+                })
+                .interact_text()?;
+            normalize_release_date(&date).map(|date| Repair::SetText {
+                id: *id,
+                column: "release_date",
+                value: date,
+            })
+        }
+        _ => {
+            let confirmed = Confirm::new()
+                .with_prompt("Repair it?")
+                .default(true)
+                .interact()?;
+            if confirmed {
+                issue.automatic_repair()
+            } else {
+                None
+            }
+        }
+    };
+    Ok(repair)
+}
+
 fn extract_year(date_str: &str) -> &str {
     if let Some(idx) = date_str.find('-') {
         &date_str[0..idx]
@@ -1,37 +1,545 @@
-use crate::metadata::models::{AlbumMetadata, AppleMusicResponse};
-use anyhow::{Context, Result};
-use reqwest::Client;
+use crate::error::Context;
+use crate::error::RequestContext;
+use crate::metadata::models::{
+    AlbumMetadata, AppleMusicItem, AppleMusicResponse, AppleMusicResult,
+};
+use crate::metadata::provider::MetadataProvider;
+use crate::{GnedbyError, Result};
+use async_trait::async_trait;
+use reqwest::{header::LOCATION, redirect::Policy, Client};
 
-const ITUNES_API_URL: &str = "https://itunes.apple.com/lookup";
+const ITUNES_API_URL: &str = "https://itunes.apple.com";
 
+/// Redirects followed when resolving a share link.
+const MAX_REDIRECTS: usize = 5;
+
+/// Width and height of artwork from Apple, in pixels. The API only returns
+/// 100×100 thumbnails, but serves any size by rewriting the URL.
+pub const DEFAULT_ARTWORK_SIZE: u32 = 600;
+
+/// Number of albums returned by an Apple Music search.
+const SEARCH_LIMIT: &str = "10";
+
+/// Looks up albums through the public iTunes Search API.
+pub struct AppleMusicProvider {
+    client: Client,
+    base_url: String,
+    storefront: Option<String>,
+    lang: Option<String>,
+    artwork_size: u32,
+}
+
+impl AppleMusicProvider {
+    pub fn new() -> Self {
+        Self::with_base_url(ITUNES_API_URL)
+    }
+
+    /// Uses another API host, e.g. a local mock server.
+    pub fn with_base_url(base_url: &str) -> Self {
+        AppleMusicProvider {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            storefront: None,
+            lang: None,
+            artwork_size: DEFAULT_ARTWORK_SIZE,
+        }
+    }
+
+    /// Sets the width and height of the artwork URLs returned, in pixels.
+    pub fn with_artwork_size(mut self, artwork_size: u32) -> Self {
+        self.artwork_size = artwork_size;
+        self
+    }
+
+    fn album_from(&self, result: AppleMusicResult) -> AlbumMetadata {
+        let mut metadata = AlbumMetadata::from(result);
+        metadata.artwork_url = apple_artwork_url(&metadata.artwork_url, self.artwork_size);
+        metadata
+    }
+
+    /// Sets the storefront (e.g. "kr") searched and looked up in, unless an
+    /// album URL names its own. Apple uses the US store otherwise.
+    pub fn with_storefront(mut self, storefront: Option<String>) -> Self {
+        self.storefront = storefront.map(|storefront| storefront.to_lowercase());
+        self
+    }
+
+    /// Sets the language of titles and genres, e.g. "ko_kr" or "ja_jp".
+    pub fn with_lang(mut self, lang: Option<String>) -> Self {
+        self.lang = lang.map(|lang| lang.to_lowercase().replace('-', "_"));
+        self
+    }
+
+    async fn request(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<Vec<AppleMusicItem>> {
+        let mut request = self
+            .client
+            .get(format!("{}/{}", self.base_url, endpoint))
+            .query(query);
+        if let Some(storefront) = &self.storefront {
+            if !query.iter().any(|(key, _)| *key == "country") {
+                request = request.query(&[("country", storefront)]);
+            }
+        }
+        if let Some(lang) = &self.lang {
+            request = request.query(&[("lang", lang)]);
+        }
+        let response = request
+            .send()
+            .await
+            .request_context("Failed to fetch album metadata from Apple Music")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(GnedbyError::from_status(
+                status,
+                format!("Apple Music API returned error status: {}", status),
+            ));
+        }
+
+        let api_response: AppleMusicResponse = response
+            .json()
+            .await
+            .request_context("Failed to parse Apple Music API response")?;
+        Ok(api_response.results)
+    }
+
+    /// Follows a share link such as `https://apple.co/3xYz` to the album or
+    /// song URL it redirects to, and returns that URL's id.
+    async fn resolve_link(&self, link: &str) -> Result<String> {
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .context("Failed to build HTTP client")?;
+        let mut url = link.to_string();
+        for _ in 0..MAX_REDIRECTS {
+            if let Some(id) = self.id_from_url(&url).filter(|_| !is_short_link(&url)) {
+                return Ok(id);
+            }
+            let response = client
+                .get(&url)
+                .send()
+                .await
+                .request_context("Failed to resolve Apple Music link")?;
+            let status = response.status();
+            if !status.is_redirection() {
+                return Err(GnedbyError::from_status(
+                    status,
+                    format!("Not an Apple Music album or song link: {}", link),
+                ));
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| {
+                    GnedbyError::Network(format!("Redirect without a location from {}", url))
+                })?;
+            url = response
+                .url()
+                .join(location)
+                .map_err(|e| GnedbyError::Network(format!("Invalid redirect location: {}", e)))?
+                .to_string();
+        }
+        Err(GnedbyError::Network(format!(
+            "Too many redirects from {}",
+            link
+        )))
+    }
+
+    /// Runs a lookup and returns the first album with its tracks.
+    async fn lookup_album(&self, query: &[(&str, &str)]) -> Result<Option<AlbumMetadata>> {
+        let mut collection = None;
+        let mut tracks = Vec::new();
+        for item in self.request("lookup", query).await? {
+            match item {
+                AppleMusicItem::Collection(result) if collection.is_none() => {
+                    collection = Some(result)
+                }
+                AppleMusicItem::Track(track) => tracks.push(track.into()),
+                _ => {}
+            }
+        }
+
+        Ok(collection.map(|collection| {
+            let mut metadata = self.album_from(collection);
+            metadata.tracks = tracks;
+            metadata
+        }))
+    }
+}
+
+impl Default for AppleMusicProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for AppleMusicProvider {
+    fn name(&self) -> &'static str {
+        "apple"
+    }
+
+    fn id_from_url(&self, url: &str) -> Option<String> {
+        if is_short_link(url) {
+            // Resolved to the full album URL by `lookup`.
+            return Some(url.to_string());
+        }
+        let (storefront, id) = apple_url_parts(url)?;
+        Some(match storefront {
+            Some(storefront) => format!("{}/{}", storefront, id),
+            None => id,
+        })
+    }
+
+    /// Takes a collection id, a song id, `<storefront>/<id>` as returned by
+    /// `id_from_url`, or a share link.
+    async fn lookup(&self, id: &str) -> Result<AlbumMetadata> {
+        let resolved;
+        let id = if id.starts_with("http://") || id.starts_with("https://") {
+            resolved = self.resolve_link(id).await?;
+            resolved.as_str()
+        } else {
+            id
+        };
+        let (storefront, id) = match id.split_once('/') {
+            Some((storefront, id)) => (Some(storefront), id),
+            None => (None, id),
+        };
+        let not_found = || GnedbyError::NotFound(format!("No album found with ID: {}", id));
+
+        let mut query = vec![("id", id), ("entity", "song")];
+        query.extend(storefront.map(|storefront| ("country", storefront)));
+        if let Some(album) = self.lookup_album(&query).await? {
+            return Ok(album);
+        }
+
+        // A song id finds only the song; look up the album it is on.
+        query.retain(|(key, _)| *key != "entity");
+        let parent = self
+            .request("lookup", &query)
+            .await?
+            .into_iter()
+            .find_map(|item| match item {
+                AppleMusicItem::Track(track) => track.collection_id,
+                _ => None,
+            })
+            .ok_or_else(not_found)?
+            .to_string();
+        query[0] = ("id", &parent);
+        query.push(("entity", "song"));
+        self.lookup_album(&query).await?.ok_or_else(not_found)
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<AlbumMetadata>> {
+        let results = self
+            .request(
+                "search",
+                &[
+                    ("term", query),
+                    ("entity", "album"),
+                    ("limit", SEARCH_LIMIT),
+                ],
+            )
+            .await?;
+        Ok(results
+            .into_iter()
+            .filter_map(|item| match item {
+                AppleMusicItem::Collection(result) => Some(self.album_from(result)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn lookup_barcode(&self, barcode: &str) -> Result<Option<AlbumMetadata>> {
+        self.lookup_album(&[("upc", barcode), ("entity", "song")])
+            .await
+    }
+}
+
+/// Fetches an album and its tracks from Apple Music by collection id, in the
+/// US storefront. Use [`AppleMusicProvider::with_storefront`] for others.
 pub async fn fetch_album_metadata(album_id: &str) -> Result<AlbumMetadata> {
-    let client = Client::new();
+    AppleMusicProvider::new().lookup(album_id).await
+}
 
-    let response = client
-        .get(ITUNES_API_URL)
-        .query(&[("id", album_id), ("entity", "album")])
-        .send()
-        .await
-        .context("Failed to fetch album metadata from Apple Music")?;
+/// Extracts the numeric collection id from an Apple Music or iTunes album URL,
+/// e.g. `https://music.apple.com/kr/album/name/1811804666?uo=4`. Song links
+/// with `?i=` give the album's id; `/song/` URLs give the song's.
+pub fn apple_album_id_from_url(url: &str) -> Option<String> {
+    apple_url_parts(url).map(|(_, id)| id)
+}
+
+/// Splits an album or song URL into its storefront, when the URL has one,
+/// and its numeric id.
+fn apple_url_parts(url: &str) -> Option<(Option<String>, String)> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let path = rest.split(['?', '#']).next()?;
+    let (host, path) = path.split_once('/')?;
+    let host = host.to_ascii_lowercase();
+    if host != "apple.com" && !host.ends_with(".apple.com") {
+        return None;
+    }
 
-    let status = response.status();
-    if !status.is_success() {
-        anyhow::bail!(
-            "Apple Music API returned error status: {} - {}",
-            status.as_u16(),
-            status.to_string()
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let kind = segments
+        .iter()
+        .position(|segment| *segment == "album" || *segment == "song")?;
+    let storefront = segments[..kind]
+        .first()
+        .filter(|segment| segment.len() == 2 && segment.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|segment| segment.to_lowercase());
+    let id = segments.last()?.trim_start_matches("id");
+
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+        Some((storefront, id.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Rewrites the size in an Apple artwork URL, such as the `100x100bb` in
+/// `https://is1-ssl.mzstatic.com/image/thumb/.../100x100bb.jpg`, to `size`.
+/// Other URLs are returned unchanged.
+pub fn apple_artwork_url(url: &str, size: u32) -> String {
+    let Some((base, file)) = url.rsplit_once('/') else {
+        return url.to_string();
+    };
+    if !base.contains("mzstatic.com/") {
+        return url.to_string();
+    }
+    let Some((width, rest)) = file.split_once('x') else {
+        return url.to_string();
+    };
+    let height_len = rest.chars().take_while(char::is_ascii_digit).count();
+    if width.is_empty() || !width.chars().all(|c| c.is_ascii_digit()) || height_len == 0 {
+        return url.to_string();
+    }
+    format!("{}/{}x{}{}", base, size, size, &rest[height_len..])
+}
+
+/// Share links from the Music app, e.g. `https://apple.co/3xYz`.
+fn is_short_link(url: &str) -> bool {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    rest.starts_with("apple.co/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    fn album_response() -> String {
+        json!({
+            "results": [
+                {
+                    "wrapperType": "collection",
+                    "collectionId": 1811804666,
+                    "artistName": "IU",
+                    "collectionName": "Palette",
+                    "collectionViewUrl": "https://music.apple.com/kr/album/palette/1811804666",
+                    "artworkUrl100": "https://is1-ssl.mzstatic.com/image/100x100bb.jpg",
+                    "country": "KOR",
+                    "releaseDate": "2017-04-21T07:00:00Z",
+                    "primaryGenreName": "K-Pop"
+                },
+                {
+                    "wrapperType": "track",
+                    "collectionId": 1811804666,
+                    "trackName": "Palette",
+                    "discNumber": 1,
+                    "trackNumber": 1,
+                    "trackTimeMillis": 217000
+                }
+            ]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn parses_album_and_song_urls() {
+        let provider = AppleMusicProvider::new();
+        let id = |url| provider.id_from_url(url);
+        assert_eq!(
+            id("https://music.apple.com/kr/album/palette/1811804666?i=1811804670").as_deref(),
+            Some("kr/1811804666")
+        );
+        assert_eq!(
+            id("https://itunes.apple.com/us/album/palette/id1811804666?uo=4").as_deref(),
+            Some("us/1811804666")
+        );
+        assert_eq!(
+            id("music.apple.com/jp/song/palette/1811804670").as_deref(),
+            Some("jp/1811804670")
         );
+        assert_eq!(
+            id("https://music.apple.com/album/1811804666").as_deref(),
+            Some("1811804666")
+        );
+        assert_eq!(
+            id("https://apple.co/3xYz").as_deref(),
+            Some("https://apple.co/3xYz")
+        );
+        assert_eq!(id("https://music.apple.com/kr/artist/iu/409076743"), None);
+        assert_eq!(
+            id("https://evilapple.com/kr/album/palette/1811804666"),
+            None
+        );
+        assert_eq!(
+            id("https://music.apple.com.evil.example/kr/album/palette/1811804666"),
+            None
+        );
+        assert_eq!(id("1811804666"), None);
+        assert_eq!(
+            apple_album_id_from_url("https://music.apple.com/kr/album/palette/1811804666")
+                .as_deref(),
+            Some("1811804666")
+        );
+    }
+
+    #[test]
+    fn rewrites_artwork_size() {
+        assert_eq!(
+            apple_artwork_url(
+                "https://is1-ssl.mzstatic.com/image/thumb/Music/v4/ab/cd/source/100x100bb.jpg",
+                1200
+            ),
+            "https://is1-ssl.mzstatic.com/image/thumb/Music/v4/ab/cd/source/1200x1200bb.jpg"
+        );
+        let other = "https://coverartarchive.org/release/x/front-500";
+        assert_eq!(apple_artwork_url(other, 600), other);
+    }
+
+    #[tokio::test]
+    async fn connection_failure_is_network_error() {
+        // Bind and release a port so nothing is listening on it.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let provider = AppleMusicProvider::with_base_url(&format!("http://127.0.0.1:{}", port));
+
+        let error = provider.lookup("1811804666").await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Network);
+    }
+
+    #[tokio::test]
+    async fn configured_storefront_and_lang() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/search")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("country".into(), "jp".into()),
+                Matcher::UrlEncoded("lang".into(), "ja_jp".into()),
+            ]))
+            .with_body(album_response())
+            .create_async()
+            .await;
+        let lookup = server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::UrlEncoded("country".into(), "kr".into()))
+            .with_body(album_response())
+            .create_async()
+            .await;
+
+        let provider = AppleMusicProvider::with_base_url(&server.url())
+            .with_storefront(Some("JP".into()))
+            .with_lang(Some("ja-JP".into()));
+        provider.search("palette").await.unwrap();
+        mock.assert_async().await;
+        // A storefront from the album URL wins over the configured one.
+        provider.lookup("kr/1811804666").await.unwrap();
+        lookup.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn storefront_is_sent_with_lookup() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), "1811804666".into()),
+                Matcher::UrlEncoded("country".into(), "kr".into()),
+            ]))
+            .with_body(album_response())
+            .create_async()
+            .await;
+
+        let provider = AppleMusicProvider::with_base_url(&server.url());
+        let album = provider.lookup("kr/1811804666").await.unwrap();
+        mock.assert_async().await;
+        assert_eq!(album.album, "Palette");
+        assert_eq!(album.storefront.as_deref(), Some("KR"));
+        assert_eq!(
+            album.artwork_url,
+            "https://is1-ssl.mzstatic.com/image/600x600bb.jpg"
+        );
+        assert_eq!(album.tracks.len(), 1);
     }
 
-    let api_response: AppleMusicResponse = response
-        .json()
-        .await
-        .context("Failed to parse Apple Music API response")?;
+    #[tokio::test]
+    async fn song_id_resolves_to_album() {
+        let mut server = Server::new_async().await;
+        let song = json!({
+            "results": [{
+                "wrapperType": "track",
+                "collectionId": 1811804666,
+                "trackName": "Palette"
+            }]
+        })
+        .to_string();
+        server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::UrlEncoded("id".into(), "1811804670".into()))
+            .with_body(song)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::UrlEncoded("id".into(), "1811804666".into()))
+            .with_body(album_response())
+            .create_async()
+            .await;
 
-    if api_response.results.is_empty() {
-        anyhow::bail!("No album found with ID: {}", album_id);
+        let provider = AppleMusicProvider::with_base_url(&server.url());
+        let album = provider.lookup("1811804670").await.unwrap();
+        assert_eq!(album.source_id, "1811804666");
     }
 
-    let first_result = api_response.results[0].clone();
-    Ok(AlbumMetadata::from(first_result))
+    #[tokio::test]
+    async fn short_link_follows_redirect() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/3xYz")
+            .with_status(301)
+            .with_header(
+                "location",
+                "https://music.apple.com/kr/album/palette/1811804666?i=1811804670",
+            )
+            .create_async()
+            .await;
+        let mock = server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::UrlEncoded("country".into(), "kr".into()))
+            .with_body(album_response())
+            .create_async()
+            .await;
+
+        let provider = AppleMusicProvider::with_base_url(&server.url());
+        let album = provider
+            .lookup(&format!("{}/3xYz", server.url()))
+            .await
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(album.source_id, "1811804666");
+    }
 }
@@ -0,0 +1,247 @@
+use crate::error::RequestContext;
+use crate::metadata::models::{AlbumMetadata, BandcampAlbum, BandcampTralbum};
+use crate::metadata::provider::MetadataProvider;
+use crate::{GnedbyError, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+
+/// Imports albums from Bandcamp album pages.
+///
+/// Bandcamp has no public API, so the album is read from the JSON-LD and the
+/// `data-tralbum` player data embedded in the page. Album ids are page URLs.
+pub struct BandcampProvider {
+    client: Client,
+}
+
+impl BandcampProvider {
+    pub fn new() -> Self {
+        BandcampProvider {
+            client: Client::new(),
+        }
+    }
+}
+
+impl Default for BandcampProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for BandcampProvider {
+    fn name(&self) -> &'static str {
+        "bandcamp"
+    }
+
+    fn id_from_url(&self, url: &str) -> Option<String> {
+        bandcamp_album_url(url)
+    }
+
+    async fn lookup(&self, id: &str) -> Result<AlbumMetadata> {
+        if !id.starts_with("http://") && !id.starts_with("https://") {
+            return Err(GnedbyError::Validation(format!(
+                "Not a Bandcamp album URL: {}",
+                id
+            )));
+        }
+        let response = self
+            .client
+            .get(id)
+            .send()
+            .await
+            .request_context("Failed to fetch Bandcamp album page")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(GnedbyError::from_status(
+                status,
+                format!("Bandcamp returned error status: {}", status),
+            ));
+        }
+
+        let html = response
+            .text()
+            .await
+            .request_context("Failed to read Bandcamp album page")?;
+        let mut metadata = parse_album_page(&html)
+            .ok_or_else(|| GnedbyError::NotFound(format!("No album found at {}", id)))?;
+        if metadata.source_url.is_empty() {
+            metadata.source_url = id.to_string();
+            metadata.source_id = id.to_string();
+        }
+        Ok(metadata)
+    }
+
+    async fn search(&self, _query: &str) -> Result<Vec<AlbumMetadata>> {
+        Ok(Vec::new())
+    }
+
+    async fn lookup_barcode(&self, _barcode: &str) -> Result<Option<AlbumMetadata>> {
+        Ok(None)
+    }
+}
+
+/// Reads an album from a Bandcamp page, preferring its JSON-LD and filling
+/// gaps from the `data-tralbum` player data.
+fn parse_album_page(html: &str) -> Option<AlbumMetadata> {
+    let json_ld = script_contents(html, "application/ld+json")
+        .and_then(|json| serde_json::from_str::<BandcampAlbum>(json).ok())
+        .map(AlbumMetadata::from);
+    let tralbum = attribute_value(html, "data-tralbum")
+        .and_then(|json| serde_json::from_str::<BandcampTralbum>(&json).ok())
+        .map(AlbumMetadata::from);
+
+    match (json_ld, tralbum) {
+        (Some(mut album), Some(tralbum)) => {
+            if album.tracks.is_empty() {
+                album.tracks = tralbum.tracks;
+            }
+            if album.release_date.is_empty() {
+                album.release_date = tralbum.release_date;
+            }
+            if album.artwork_url.is_empty() {
+                album.artwork_url = tralbum.artwork_url;
+            }
+            if album.source_url.is_empty() {
+                album.source_id = tralbum.source_id;
+                album.source_url = tralbum.source_url;
+            }
+            Some(album)
+        }
+        (album, tralbum) => album.or(tralbum),
+    }
+}
+
+/// Returns the body of the first `<script type="...">` element of a type.
+fn script_contents<'a>(html: &'a str, script_type: &str) -> Option<&'a str> {
+    let marker = format!("type=\"{}\"", script_type);
+    let start = html.find(&marker)?;
+    let body = &html[start..];
+    let body = &body[body.find('>')? + 1..];
+    Some(body[..body.find("</script>")?].trim())
+}
+
+/// Returns an HTML attribute's value with its entities decoded.
+fn attribute_value(html: &str, name: &str) -> Option<String> {
+    let marker = format!("{}=\"", name);
+    let start = html.find(&marker)? + marker.len();
+    let value = &html[start..];
+    Some(decode_entities(&value[..value.find('"')?]))
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Normalizes a URL like `https://artist.bandcamp.com/album/name?from=...`,
+/// returning `None` for anything but a bandcamp.com album page.
+pub fn bandcamp_album_url(url: &str) -> Option<String> {
+    let url = url.split(['?', '#']).next()?.trim_end_matches('/');
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/')?;
+    if !host.ends_with(".bandcamp.com") || !path.starts_with("album/") {
+        return None;
+    }
+    Some(format!("https://{}/{}", host, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use mockito::Server;
+
+    const JSON_LD_PAGE: &str = r#"<html><head>
+<script type="application/ld+json">
+{
+  "@type": "MusicAlbum",
+  "@id": "https://artist.bandcamp.com/album/xyz",
+  "name": "Tape Loops",
+  "byArtist": { "@type": "MusicGroup", "name": "Night Tapes",
+                "foundingLocation": { "name": "Seoul, South Korea" } },
+  "datePublished": "20 Oct 2023 00:00:00 GMT",
+  "image": "https://f4.bcbits.com/img/a123_10.jpg",
+  "keywords": ["ambient", "drone", "tape", "Seoul"],
+  "track": { "numberOfItems": 2, "itemListElement": [
+    { "position": 1, "item": { "name": "Side A", "duration": "P00H12M03S" } },
+    { "position": 2, "item": { "name": "Side B", "duration": "P00H11M30S" } }
+  ] }
+}
+</script></head><body></body></html>"#;
+
+    const TRALBUM_PAGE: &str = r#"<html><body>
+<script data-tralbum="{&quot;artist&quot;:&quot;Night Tapes&quot;,&quot;current&quot;:{&quot;title&quot;:&quot;Tape Loops&quot;,&quot;release_date&quot;:&quot;20 Oct 2023 00:00:00 GMT&quot;},&quot;art_id&quot;:123,&quot;url&quot;:&quot;https://artist.bandcamp.com/album/xyz&quot;,&quot;trackinfo&quot;:[{&quot;title&quot;:&quot;Side A&quot;,&quot;track_num&quot;:1,&quot;duration&quot;:723.0}]}"></script>
+</body></html>"#;
+
+    #[test]
+    fn parses_json_ld() {
+        let album = parse_album_page(JSON_LD_PAGE).unwrap();
+        assert_eq!(album.provider, "bandcamp");
+        assert_eq!(album.artist, "Night Tapes");
+        assert_eq!(album.album, "Tape Loops");
+        assert_eq!(album.release_date, "2023-10-20");
+        assert_eq!(album.genre, "ambient, drone, tape");
+        assert_eq!(album.origin_country.as_deref(), Some("KR"));
+        assert_eq!(album.artwork_url, "https://f4.bcbits.com/img/a123_10.jpg");
+        assert_eq!(album.source_id, "https://artist.bandcamp.com/album/xyz");
+        assert_eq!(album.tracks.len(), 2);
+        assert_eq!(album.tracks[0].duration_ms, Some(723_000));
+    }
+
+    #[test]
+    fn falls_back_to_tralbum() {
+        let album = parse_album_page(TRALBUM_PAGE).unwrap();
+        assert_eq!(album.artist, "Night Tapes");
+        assert_eq!(album.release_date, "2023-10-20");
+        assert_eq!(album.artwork_url, "https://f4.bcbits.com/img/a123_10.jpg");
+        assert_eq!(album.tracks[0].duration_ms, Some(723_000));
+    }
+
+    #[tokio::test]
+    async fn lookup_fetches_page() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/album/xyz")
+            .with_body(JSON_LD_PAGE)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/album/empty")
+            .with_body("<html></html>")
+            .create_async()
+            .await;
+
+        let provider = BandcampProvider::new();
+        let album = provider
+            .lookup(&format!("{}/album/xyz", server.url()))
+            .await
+            .unwrap();
+        assert_eq!(album.album, "Tape Loops");
+
+        let err = provider
+            .lookup(&format!("{}/album/empty", server.url()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn recognizes_album_urls() {
+        assert_eq!(
+            bandcamp_album_url("https://artist.bandcamp.com/album/xyz?from=discover").as_deref(),
+            Some("https://artist.bandcamp.com/album/xyz")
+        );
+        assert_eq!(
+            bandcamp_album_url("https://artist.bandcamp.com/track/song"),
+            None
+        );
+        assert_eq!(bandcamp_album_url("https://example.com/album/xyz"), None);
+    }
+}
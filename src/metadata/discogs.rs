@@ -0,0 +1,318 @@
+use crate::error::RequestContext;
+use crate::metadata::models::{AlbumMetadata, DiscogsRelease, DiscogsSearchResponse};
+use crate::metadata::provider::MetadataProvider;
+use crate::{GnedbyError, Result};
+use async_trait::async_trait;
+use reqwest::{header::AUTHORIZATION, Client};
+
+const DISCOGS_API_URL: &str = "https://api.discogs.com";
+
+/// Discogs rejects requests without a User-Agent naming the application.
+const USER_AGENT: &str = concat!(
+    "gnedby/",
+    env!("CARGO_PKG_VERSION"),
+    " +",
+    env!("CARGO_PKG_REPOSITORY")
+);
+
+const SEARCH_LIMIT: &str = "10";
+
+/// Looks up releases in the Discogs database.
+///
+/// Release lookups work without a token; searching, including by barcode,
+/// needs a personal access token from the Discogs developer settings.
+pub struct DiscogsProvider {
+    client: Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl DiscogsProvider {
+    pub fn new(token: Option<String>) -> Self {
+        Self::with_base_url(DISCOGS_API_URL, token)
+    }
+
+    /// Uses another API host, e.g. a local mock server.
+    pub fn with_base_url(base_url: &str, token: Option<String>) -> Self {
+        DiscogsProvider {
+            client: Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.filter(|token| !token.is_empty()),
+        }
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let mut request = self
+            .client
+            .get(format!("{}/{}", self.base_url, path))
+            .query(query);
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("Discogs token={}", token));
+        }
+        let response = request
+            .send()
+            .await
+            .request_context("Failed to fetch album metadata from Discogs")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(GnedbyError::from_status(
+                status,
+                format!("Discogs API returned error status: {}", status),
+            ));
+        }
+
+        let body = response
+            .json()
+            .await
+            .request_context("Failed to parse Discogs API response")?;
+        Ok(body)
+    }
+
+    async fn search_releases(&self, query: &[(&str, &str)]) -> Result<Vec<AlbumMetadata>> {
+        if self.token.is_none() {
+            return Err(GnedbyError::Auth(
+                "Searching Discogs needs a personal access token. \
+                 Set one with `gnedby provider config set discogs_token <token>`."
+                    .to_string(),
+            ));
+        }
+        let response: DiscogsSearchResponse = self.request("database/search", query).await?;
+        Ok(response
+            .results
+            .into_iter()
+            .map(AlbumMetadata::from)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for DiscogsProvider {
+    fn name(&self) -> &'static str {
+        "discogs"
+    }
+
+    fn id_from_url(&self, url: &str) -> Option<String> {
+        discogs_release_id_from_url(url)
+    }
+
+    async fn lookup(&self, id: &str) -> Result<AlbumMetadata> {
+        let id = id.trim_start_matches(['r', 'R']);
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(GnedbyError::Validation(format!(
+                "Not a Discogs release ID: {}",
+                id
+            )));
+        }
+        let release: DiscogsRelease = self
+            .request(&format!("releases/{}", id), &[])
+            .await
+            .map_err(|e| match e {
+                GnedbyError::NotFound(_) => {
+                    GnedbyError::NotFound(format!("No release found with ID: {}", id))
+                }
+                e => e,
+            })?;
+        Ok(AlbumMetadata::from(release))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<AlbumMetadata>> {
+        self.search_releases(&[
+            ("q", query),
+            ("type", "release"),
+            ("per_page", SEARCH_LIMIT),
+        ])
+        .await
+    }
+
+    async fn lookup_barcode(&self, barcode: &str) -> Result<Option<AlbumMetadata>> {
+        let barcode: String = barcode.chars().filter(char::is_ascii_digit).collect();
+        if barcode.is_empty() {
+            return Ok(None);
+        }
+        let Some(release) = self
+            .search_releases(&[
+                ("barcode", &barcode),
+                ("type", "release"),
+                ("per_page", "1"),
+            ])
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Ok(None);
+        };
+        // Search results carry no track list, so fetch the full release.
+        self.lookup(&release.source_id).await.map(Some)
+    }
+}
+
+/// Extracts the release id from a URL like
+/// `https://www.discogs.com/release/249504-Rick-Astley-Never-Gonna-Give-You-Up`.
+pub fn discogs_release_id_from_url(url: &str) -> Option<String> {
+    if !url.contains("discogs.com/") {
+        return None;
+    }
+    let path = url.split(['?', '#']).next()?;
+    let mut segments = path
+        .split('/')
+        .skip_while(|segment| *segment != "release" && *segment != "releases");
+    segments.next()?;
+    let id: String = segments
+        .next()?
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    Some(id).filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{ArtistCredit, ArtistRole};
+    use crate::{ErrorKind, Format};
+    use mockito::{Matcher, Server};
+    use serde_json::{json, Value};
+
+    fn release() -> Value {
+        json!({
+            "id": 249504,
+            "title": "Never Gonna Give You Up",
+            "year": 1987,
+            "released": "1987-00-00",
+            "country": "UK",
+            "uri": "https://www.discogs.com/release/249504-Rick-Astley-Never-Gonna-Give-You-Up",
+            "artists": [
+                { "name": "Rick Astley (2)", "anv": "", "join": "&" },
+                { "name": "Stock, Aitken & Waterman", "anv": "SAW", "join": "" }
+            ],
+            "labels": [{ "name": "RCA", "catno": "PB 41447" }],
+            "formats": [
+                { "name": "Vinyl", "qty": "1", "descriptions": ["7\"", "45 RPM", "Single"] }
+            ],
+            "genres": ["Electronic", "Pop"],
+            "styles": ["Synth-pop"],
+            "images": [
+                { "type": "secondary", "uri": "https://i.discogs.com/back.jpg" },
+                { "type": "primary", "uri": "https://i.discogs.com/front.jpg" }
+            ],
+            "identifiers": [{ "type": "Barcode", "value": "5 012394 144777" }],
+            "tracklist": [
+                { "position": "", "type_": "heading", "title": "Side One", "duration": "" },
+                { "position": "A", "type_": "track", "title": "Never Gonna Give You Up", "duration": "3:32" },
+                { "position": "B", "type_": "track", "title": "Instrumental", "duration": "" }
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn lookup_maps_release() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/releases/249504")
+            .with_header("content-type", "application/json")
+            .with_body(release().to_string())
+            .create_async()
+            .await;
+
+        let provider = DiscogsProvider::with_base_url(&server.url(), None);
+        let metadata = provider.lookup("249504").await.unwrap();
+        mock.assert_async().await;
+
+        assert_eq!(metadata.provider, "discogs");
+        assert_eq!(metadata.source_id, "249504");
+        assert_eq!(metadata.artist, "Rick Astley & SAW");
+        assert_eq!(
+            metadata.artists,
+            [
+                ArtistCredit::new("Rick Astley", ArtistRole::Primary),
+                ArtistCredit::new("SAW", ArtistRole::Primary),
+            ]
+        );
+        assert_eq!(metadata.release_date, "1987-01-01");
+        assert_eq!(metadata.origin_country.as_deref(), Some("GB"));
+        assert_eq!(metadata.genre, "Synth-pop");
+        assert_eq!(metadata.label.as_deref(), Some("RCA"));
+        assert_eq!(metadata.catalog_number.as_deref(), Some("PB 41447"));
+        assert_eq!(metadata.barcode.as_deref(), Some("5012394144777"));
+        assert_eq!(metadata.artwork_url, "https://i.discogs.com/front.jpg");
+        assert_eq!(metadata.format, Some(Format::Lp));
+        assert_eq!(
+            metadata.format_details.as_deref(),
+            Some("Vinyl, 7\", 45 RPM, Single")
+        );
+        assert_eq!(metadata.tracks.len(), 2);
+        assert_eq!(metadata.tracks[0].duration_ms, Some(212_000));
+        assert_eq!(metadata.tracks[1].track_number, 2);
+        assert_eq!(metadata.tracks[1].duration_ms, None);
+    }
+
+    #[tokio::test]
+    async fn sends_token() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/database/search")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "Discogs token=secret")
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({ "results": [{ "id": 1, "title": "Nell (2) - Healing Process" }] })
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let provider = DiscogsProvider::with_base_url(&server.url(), Some("secret".into()));
+        let results = provider.search("nell").await.unwrap();
+        mock.assert_async().await;
+        assert_eq!(results[0].artist, "Nell");
+        assert_eq!(results[0].album, "Healing Process");
+    }
+
+    #[tokio::test]
+    async fn search_without_token_is_auth_error() {
+        let provider = DiscogsProvider::with_base_url("http://127.0.0.1:9", None);
+        let err = provider.search("nell").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Auth);
+    }
+
+    #[tokio::test]
+    async fn missing_release_is_not_found() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/releases/1")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let provider = DiscogsProvider::with_base_url(&server.url(), None);
+        let err = provider.lookup("1").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn parses_release_urls() {
+        assert_eq!(
+            discogs_release_id_from_url(
+                "https://www.discogs.com/release/249504-Rick-Astley-Never-Gonna-Give-You-Up"
+            )
+            .as_deref(),
+            Some("249504")
+        );
+        assert_eq!(
+            discogs_release_id_from_url("https://www.discogs.com/ko/release/42?x=1").as_deref(),
+            Some("42")
+        );
+        assert_eq!(
+            discogs_release_id_from_url("https://www.discogs.com/master/42"),
+            None
+        );
+    }
+}
@@ -1,4 +1,13 @@
 mod apple;
-mod models;
+mod bandcamp;
+mod discogs;
+pub mod models;
+mod musicbrainz;
+mod provider;
 
 pub use apple::*;
+pub use bandcamp::*;
+pub use discogs::*;
+pub use models::{AlbumMetadata, TrackMetadata};
+pub use musicbrainz::*;
+pub use provider::*;
@@ -1,23 +1,137 @@
+use crate::country::normalize_country;
+use crate::db::doctor::normalize_release_date;
+use crate::db::{is_various_artists, Album, ArtistCredit, ArtistRole, Format, Track};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AlbumMetadata {
+    /// Name of the provider the metadata came from, e.g. "apple".
+    pub provider: String,
+    /// The album's id at that provider.
+    pub source_id: String,
     pub artist: String,
+    /// Artist credits as the provider structures them. Empty when the provider
+    /// only has the display string, which is then parsed instead.
+    #[serde(default)]
+    pub artists: Vec<ArtistCredit>,
     pub album: String,
     pub genre: String,
     pub release_date: String,
     pub source_url: String,
     pub artwork_url: String,
-    pub country: String,
+    /// Apple Music storefront (ISO alpha-2), for Apple albums.
+    pub storefront: Option<String>,
+    pub origin_country: Option<String>,
+    pub label: Option<String>,
+    pub catalog_number: Option<String>,
+    /// UPC or EAN printed on the release.
+    pub barcode: Option<String>,
+    /// Physical format, when the provider knows the exact release.
+    pub format: Option<Format>,
+    /// Format description such as "Vinyl, LP, Album, Limited Edition".
+    pub format_details: Option<String>,
+    pub compilation: bool,
+    /// Number of tracks, which search results report without the tracks.
+    pub track_count: Option<i64>,
+    pub tracks: Vec<TrackMetadata>,
+}
+
+impl AlbumMetadata {
+    /// Builds the album to store in the collection, and its tracks.
+    pub fn into_album(self, format: Format) -> (Album, Vec<Track>) {
+        let tracks = self.tracks.into_iter().map(Track::from).collect();
+        let album = Album {
+            id: None,
+            artists: if self.artists.is_empty() {
+                ArtistCredit::parse(&self.artist)
+            } else {
+                self.artists
+            },
+            artist: self.artist,
+            album: self.album,
+            genre: self.genre,
+            release_date: self.release_date,
+            format,
+            source_url: self.source_url,
+            storefront: self.storefront,
+            origin_country: self.origin_country,
+            artwork_url: self.artwork_url,
+            provider: Some(self.provider),
+            source_id: Some(self.source_id),
+            label: self.label,
+            catalog_number: self.catalog_number,
+            barcode: self.barcode,
+            format_details: self.format_details,
+            compilation: self.compilation,
+            genres: Vec::new(),
+            edited_fields: Vec::new(),
+        };
+        (album, tracks)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TrackMetadata {
+    pub disc_number: i64,
+    pub track_number: i64,
+    pub title: String,
+    pub duration_ms: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AppleMusicResponse {
-    pub results: Vec<AppleMusicResult>,
+    pub results: Vec<AppleMusicItem>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "wrapperType", rename_all = "lowercase")]
+pub enum AppleMusicItem {
+    Collection(AppleMusicResult),
+    Track(AppleMusicTrack),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AppleMusicTrack {
+    #[serde(rename = "trackName")]
+    pub track_name: String,
+
+    #[serde(rename = "discNumber", default = "default_number")]
+    pub disc_number: i64,
+
+    #[serde(rename = "trackNumber", default = "default_number")]
+    pub track_number: i64,
+
+    #[serde(rename = "trackTimeMillis")]
+    pub track_time_millis: Option<i64>,
+
+    /// The album the track is on.
+    #[serde(rename = "collectionId")]
+    pub collection_id: Option<i64>,
+}
+
+fn default_number() -> i64 {
+    1
+}
+
+impl From<AppleMusicTrack> for TrackMetadata {
+    fn from(track: AppleMusicTrack) -> Self {
+        TrackMetadata {
+            disc_number: track.disc_number,
+            track_number: track.track_number,
+            title: track.track_name,
+            duration_ms: track.track_time_millis,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppleMusicResult {
+    #[serde(rename = "collectionId")]
+    pub collection_id: i64,
+
     #[serde(rename = "artistName")]
     pub artist_name: String,
 
@@ -37,18 +151,834 @@ pub struct AppleMusicResult {
 
     #[serde(rename = "primaryGenreName")]
     pub primary_genre_name: String,
+
+    #[serde(rename = "trackCount")]
+    pub track_count: Option<i64>,
 }
 
 impl From<AppleMusicResult> for AlbumMetadata {
     fn from(result: AppleMusicResult) -> Self {
+        let compilation = is_various_artists(&result.artist_name);
         AlbumMetadata {
+            provider: "apple".to_string(),
+            source_id: result.collection_id.to_string(),
             artist: result.artist_name,
+            artists: Vec::new(),
             album: result.collection_name,
             genre: result.primary_genre_name,
             release_date: result.release_date,
             source_url: result.collection_view_url,
             artwork_url: result.artwork_url_100,
-            country: result.country,
+            storefront: Some(
+                normalize_country(&result.country)
+                    .map(|c| c.alpha2.to_string())
+                    .unwrap_or(result.country),
+            ),
+            origin_country: None,
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format: None,
+            format_details: None,
+            compilation,
+            track_count: result.track_count,
+            tracks: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MusicBrainzSearchResponse {
+    #[serde(default)]
+    pub releases: Vec<MusicBrainzRelease>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzRelease {
+    pub id: String,
+    pub title: String,
+    pub date: Option<String>,
+    pub country: Option<String>,
+    pub barcode: Option<String>,
+    #[serde(rename = "artist-credit", default)]
+    pub artist_credit: Vec<MusicBrainzArtistCredit>,
+    #[serde(rename = "label-info", default)]
+    pub label_info: Vec<MusicBrainzLabelInfo>,
+    /// Total number of tracks, given in search results.
+    #[serde(rename = "track-count")]
+    pub track_count: Option<i64>,
+    #[serde(default)]
+    pub media: Vec<MusicBrainzMedium>,
+    #[serde(default)]
+    pub genres: Vec<MusicBrainzGenre>,
+    #[serde(rename = "release-group")]
+    pub release_group: Option<MusicBrainzReleaseGroup>,
+    #[serde(rename = "cover-art-archive")]
+    pub cover_art_archive: Option<MusicBrainzCoverArt>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzArtistCredit {
+    pub name: String,
+    #[serde(default)]
+    pub joinphrase: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzLabelInfo {
+    #[serde(rename = "catalog-number")]
+    pub catalog_number: Option<String>,
+    pub label: Option<MusicBrainzLabel>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzLabel {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzMedium {
+    #[serde(default = "default_number")]
+    pub position: i64,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub tracks: Vec<MusicBrainzTrack>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzTrack {
+    pub position: i64,
+    pub title: String,
+    pub length: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzGenre {
+    pub name: String,
+    #[serde(default)]
+    pub count: i64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzReleaseGroup {
+    #[serde(default)]
+    pub genres: Vec<MusicBrainzGenre>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MusicBrainzCoverArt {
+    #[serde(default)]
+    pub front: bool,
+}
+
+/// Builds credits from a provider's structured artist list of (name, join
+/// phrase) pairs. Names after a "feat." join phrase are featured artists.
+fn credits_from_parts<'a>(
+    parts: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<ArtistCredit> {
+    let mut credits = Vec::new();
+    let mut role = ArtistRole::Primary;
+    for (name, join) in parts {
+        let name = name.trim();
+        if is_various_artists(name) {
+            return ArtistCredit::parse(name);
+        }
+        if !name.is_empty() {
+            credits.push(ArtistCredit::new(name, role));
+        }
+        let join = join.trim().trim_start_matches('(').to_lowercase();
+        if join.starts_with("feat") || join.starts_with("ft") {
+            role = ArtistRole::Featuring;
+        }
+    }
+    credits
+}
+
+/// Cover Art Archive image of a release's front cover.
+const COVER_ART_URL: &str = "https://coverartarchive.org/release";
+
+/// Number of genres kept from a MusicBrainz release, most voted first.
+const MUSICBRAINZ_GENRES: usize = 3;
+
+impl From<MusicBrainzRelease> for AlbumMetadata {
+    fn from(release: MusicBrainzRelease) -> Self {
+        let artist: String = release
+            .artist_credit
+            .iter()
+            .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+            .collect();
+        let artists = credits_from_parts(
+            release
+                .artist_credit
+                .iter()
+                .map(|credit| (credit.name.as_str(), credit.joinphrase.as_str())),
+        );
+
+        // Release genres are often empty while the release group has votes.
+        let mut genres = release.genres;
+        if genres.is_empty() {
+            genres = release
+                .release_group
+                .map(|group| group.genres)
+                .unwrap_or_default();
+        }
+        genres.sort_by_key(|genre| std::cmp::Reverse(genre.count));
+        let genre = genres
+            .iter()
+            .take(MUSICBRAINZ_GENRES)
+            .map(|genre| genre.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let label_info = release.label_info.first();
+        let tracks = release
+            .media
+            .iter()
+            .flat_map(|medium| {
+                medium.tracks.iter().map(|track| TrackMetadata {
+                    disc_number: medium.position,
+                    track_number: track.position,
+                    title: track.title.clone(),
+                    duration_ms: track.length,
+                })
+            })
+            .collect();
+
+        AlbumMetadata {
+            provider: "musicbrainz".to_string(),
+            source_url: format!("https://musicbrainz.org/release/{}", release.id),
+            artwork_url: if release.cover_art_archive.is_some_and(|art| art.front) {
+                format!("{}/{}/front-500", COVER_ART_URL, release.id)
+            } else {
+                String::new()
+            },
+            source_id: release.id,
+            compilation: is_various_artists(&artist),
+            artist,
+            artists,
+            album: release.title,
+            genre,
+            // MusicBrainz leaves out unknown parts, e.g. "2017" or "2017-04".
+            release_date: release
+                .date
+                .as_deref()
+                .and_then(normalize_release_date)
+                .unwrap_or_default(),
+            storefront: None,
+            origin_country: release
+                .country
+                .as_deref()
+                .and_then(normalize_country)
+                .map(|c| c.alpha2.to_string()),
+            label: label_info
+                .and_then(|info| info.label.as_ref())
+                .map(|label| label.name.clone()),
+            catalog_number: label_info
+                .and_then(|info| info.catalog_number.clone())
+                .filter(|number| !number.is_empty()),
+            barcode: release.barcode.filter(|barcode| !barcode.is_empty()),
+            format: release
+                .media
+                .first()
+                .and_then(|medium| medium.format.as_deref())
+                .and_then(format_from_media),
+            format_details: None,
+            track_count: release.track_count,
+            tracks,
+        }
+    }
+}
+
+/// Maps a medium name used by MusicBrainz and Discogs, such as "Vinyl",
+/// "12\" Vinyl" or "Cassette", to a collection format.
+pub fn format_from_media(name: &str) -> Option<Format> {
+    let name = name.to_lowercase();
+    if name.contains("vinyl") || name == "lp" {
+        Some(Format::Lp)
+    } else if name.contains("cd") {
+        Some(Format::Cd)
+    } else if name.contains("cassette") {
+        Some(Format::Tape)
+    } else if name.contains("usb") {
+        Some(Format::Usb)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsRelease {
+    pub id: i64,
+    pub title: String,
+    #[serde(default)]
+    pub artists: Vec<DiscogsArtist>,
+    pub year: Option<i32>,
+    pub released: Option<String>,
+    pub country: Option<String>,
+    pub uri: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<DiscogsLabel>,
+    #[serde(default)]
+    pub formats: Vec<DiscogsFormat>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub styles: Vec<String>,
+    #[serde(default)]
+    pub images: Vec<DiscogsImage>,
+    #[serde(default)]
+    pub tracklist: Vec<DiscogsTrack>,
+    #[serde(default)]
+    pub identifiers: Vec<DiscogsIdentifier>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsArtist {
+    pub name: String,
+    /// Artist name variation, as credited on this release.
+    #[serde(default)]
+    pub anv: String,
+    #[serde(default)]
+    pub join: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsLabel {
+    pub name: String,
+    pub catno: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsFormat {
+    pub name: String,
+    pub qty: Option<String>,
+    #[serde(default)]
+    pub descriptions: Vec<String>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsImage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsTrack {
+    pub position: String,
+    #[serde(rename = "type_", default)]
+    pub kind: String,
+    pub title: String,
+    #[serde(default)]
+    pub duration: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsIdentifier {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscogsSearchResponse {
+    #[serde(default)]
+    pub results: Vec<DiscogsSearchResult>,
+}
+
+/// A release in Discogs search results, which carry less than a full release.
+#[derive(Debug, Deserialize)]
+pub struct DiscogsSearchResult {
+    pub id: i64,
+    /// "Artist - Title".
+    pub title: String,
+    pub year: Option<String>,
+    pub country: Option<String>,
+    #[serde(default)]
+    pub label: Vec<String>,
+    pub catno: Option<String>,
+    #[serde(default)]
+    pub format: Vec<String>,
+    #[serde(default)]
+    pub genre: Vec<String>,
+    #[serde(default)]
+    pub style: Vec<String>,
+    pub cover_image: Option<String>,
+    pub uri: Option<String>,
+}
+
+/// Number of genres or styles kept from a Discogs release.
+const DISCOGS_GENRES: usize = 3;
+
+impl From<DiscogsRelease> for AlbumMetadata {
+    fn from(release: DiscogsRelease) -> Self {
+        let mut artist = String::new();
+        for (index, credit) in release.artists.iter().enumerate() {
+            let name = if credit.anv.is_empty() {
+                &credit.name
+            } else {
+                &credit.anv
+            };
+            artist.push_str(strip_discogs_suffix(name));
+            if index + 1 < release.artists.len() {
+                match credit.join.trim() {
+                    "" | "," => artist.push_str(", "),
+                    join => artist.push_str(&format!(" {} ", join)),
+                }
+            }
+        }
+        let artists = credits_from_parts(release.artists.iter().map(|credit| {
+            let name = if credit.anv.is_empty() {
+                &credit.name
+            } else {
+                &credit.anv
+            };
+            (strip_discogs_suffix(name), credit.join.as_str())
+        }));
+
+        let genres = if release.styles.is_empty() {
+            &release.genres
+        } else {
+            &release.styles
+        };
+        let label = release.labels.first();
+        let image = release
+            .images
+            .iter()
+            .find(|image| image.kind == "primary")
+            .or(release.images.first());
+
+        let format_details = release
+            .formats
+            .iter()
+            .map(|format| {
+                let mut parts = Vec::new();
+                match format.qty.as_deref() {
+                    Some(qty) if qty != "1" && !qty.is_empty() => {
+                        parts.push(format!("{} x {}", qty, format.name))
+                    }
+                    _ => parts.push(format.name.clone()),
+                }
+                parts.extend(format.descriptions.iter().cloned());
+                parts.extend(format.text.clone().filter(|text| !text.is_empty()));
+                parts.join(", ")
+            })
+            .collect::<Vec<_>>()
+            .join(" + ");
+
+        AlbumMetadata {
+            provider: "discogs".to_string(),
+            source_id: release.id.to_string(),
+            compilation: is_various_artists(&artist),
+            artist,
+            artists,
+            album: release.title,
+            genre: genres
+                .iter()
+                .take(DISCOGS_GENRES)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", "),
+            release_date: discogs_release_date(release.released.as_deref(), release.year),
+            source_url: release
+                .uri
+                .unwrap_or_else(|| format!("https://www.discogs.com/release/{}", release.id)),
+            artwork_url: image.map(|image| image.uri.clone()).unwrap_or_default(),
+            storefront: None,
+            origin_country: release
+                .country
+                .as_deref()
+                .and_then(normalize_country)
+                .map(|c| c.alpha2.to_string()),
+            label: label.map(|label| strip_discogs_suffix(&label.name).to_string()),
+            catalog_number: label
+                .and_then(|label| label.catno.clone())
+                .filter(|catno| !catno.is_empty() && catno != "none"),
+            barcode: release
+                .identifiers
+                .iter()
+                .find(|identifier| identifier.kind == "Barcode")
+                .map(|identifier| {
+                    identifier
+                        .value
+                        .chars()
+                        .filter(char::is_ascii_digit)
+                        .collect()
+                }),
+            format: release
+                .formats
+                .first()
+                .and_then(|format| format_from_media(&format.name)),
+            format_details: Some(format_details).filter(|details| !details.is_empty()),
+            track_count: None,
+            tracks: discogs_tracks(&release.tracklist),
+        }
+    }
+}
+
+impl From<DiscogsSearchResult> for AlbumMetadata {
+    fn from(result: DiscogsSearchResult) -> Self {
+        let (artist, album) = match result.title.split_once(" - ") {
+            Some((artist, album)) => (strip_discogs_suffix(artist).to_string(), album.to_string()),
+            None => (String::new(), result.title.clone()),
+        };
+        let genres = if result.style.is_empty() {
+            &result.genre
+        } else {
+            &result.style
+        };
+
+        AlbumMetadata {
+            provider: "discogs".to_string(),
+            source_id: result.id.to_string(),
+            compilation: is_various_artists(&artist),
+            artist,
+            artists: Vec::new(),
+            album,
+            genre: genres
+                .iter()
+                .take(DISCOGS_GENRES)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", "),
+            release_date: discogs_release_date(None, result.year.and_then(|y| y.parse().ok())),
+            source_url: format!("https://www.discogs.com/release/{}", result.id),
+            artwork_url: result.cover_image.unwrap_or_default(),
+            storefront: None,
+            origin_country: result
+                .country
+                .as_deref()
+                .and_then(normalize_country)
+                .map(|c| c.alpha2.to_string()),
+            label: result.label.first().cloned(),
+            catalog_number: result.catno.filter(|catno| !catno.is_empty()),
+            barcode: None,
+            format: result
+                .format
+                .first()
+                .and_then(|name| format_from_media(name)),
+            format_details: Some(result.format.join(", ")).filter(|details| !details.is_empty()),
+            track_count: None,
+            tracks: Vec::new(),
+        }
+    }
+}
+
+/// Discogs tells apart artists and labels with the same name by a number,
+/// e.g. "Nell (2)".
+fn strip_discogs_suffix(name: &str) -> &str {
+    match name.rsplit_once(" (") {
+        Some((base, number))
+            if number.ends_with(')')
+                && number[..number.len() - 1]
+                    .chars()
+                    .all(|c| c.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => name,
+    }
+}
+
+/// Discogs writes unknown parts of a date as zeros, e.g. "1994-00-00".
+fn discogs_release_date(released: Option<&str>, year: Option<i32>) -> String {
+    let released = released.map(|date| date.trim_end_matches("-00"));
+    released
+        .and_then(normalize_release_date)
+        .or_else(|| {
+            year.filter(|year| *year > 0)
+                .map(|year| format!("{}-01-01", year))
+        })
+        .unwrap_or_default()
+}
+
+/// Numbers Discogs tracks, whose positions look like "A1", "2-05" or "3".
+/// Sides of a record stay on one disc; "2-05" is track 5 of disc 2.
+fn discogs_tracks(tracklist: &[DiscogsTrack]) -> Vec<TrackMetadata> {
+    let mut tracks: Vec<TrackMetadata> = Vec::new();
+    for track in tracklist.iter().filter(|track| track.kind == "track") {
+        let disc_number = track
+            .position
+            .split_once(['-', '.'])
+            .and_then(|(disc, _)| {
+                disc.trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                    .parse()
+                    .ok()
+            })
+            .unwrap_or(1);
+        let track_number = tracks
+            .iter()
+            .filter(|previous| previous.disc_number == disc_number)
+            .count() as i64
+            + 1;
+        tracks.push(TrackMetadata {
+            disc_number,
+            track_number,
+            title: track.title.clone(),
+            duration_ms: parse_duration(&track.duration),
+        });
+    }
+    tracks
+}
+
+/// Parses "m:ss" or "h:mm:ss" into milliseconds.
+fn parse_duration(duration: &str) -> Option<i64> {
+    let mut seconds = 0;
+    for part in duration.trim().split(':') {
+        seconds = seconds * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(seconds * 1000).filter(|ms| *ms > 0)
+}
+
+/// The `MusicAlbum` JSON-LD a Bandcamp album page embeds.
+#[derive(Debug, Deserialize)]
+pub struct BandcampAlbum {
+    pub name: String,
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "byArtist")]
+    pub by_artist: Option<BandcampArtist>,
+    pub publisher: Option<BandcampArtist>,
+    #[serde(rename = "datePublished")]
+    pub date_published: Option<String>,
+    pub image: Option<StringOrList>,
+    pub keywords: Option<StringOrList>,
+    pub track: Option<BandcampTrackList>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampArtist {
+    pub name: String,
+    #[serde(rename = "foundingLocation")]
+    pub founding_location: Option<BandcampLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampLocation {
+    /// Free text such as "Seoul, South Korea".
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampTrackList {
+    #[serde(rename = "itemListElement", default)]
+    pub items: Vec<BandcampTrackItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampTrackItem {
+    pub position: i64,
+    pub item: BandcampRecording,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampRecording {
+    pub name: String,
+    /// ISO 8601 duration, e.g. "P00H03M12S".
+    pub duration: Option<String>,
+}
+
+/// JSON-LD allows most values to be either a single string or a list.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrList {
+    String(String),
+    List(Vec<String>),
+}
+
+impl StringOrList {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            StringOrList::String(value) => value
+                .split(',')
+                .map(|part| part.trim().to_string())
+                .filter(|part| !part.is_empty())
+                .collect(),
+            StringOrList::List(values) => values,
+        }
+    }
+}
+
+/// The player data in a Bandcamp page's `data-tralbum` attribute, used when
+/// the page has no JSON-LD.
+#[derive(Debug, Deserialize)]
+pub struct BandcampTralbum {
+    pub artist: String,
+    pub current: BandcampCurrent,
+    pub art_id: Option<i64>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub trackinfo: Vec<BandcampTrackInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampCurrent {
+    pub title: String,
+    pub release_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BandcampTrackInfo {
+    pub title: String,
+    pub track_num: Option<i64>,
+    /// Seconds.
+    pub duration: Option<f64>,
+}
+
+/// Number of Bandcamp tags kept as genres.
+const BANDCAMP_GENRES: usize = 3;
+
+impl From<BandcampAlbum> for AlbumMetadata {
+    fn from(album: BandcampAlbum) -> Self {
+        let artist = album
+            .by_artist
+            .as_ref()
+            .or(album.publisher.as_ref())
+            .map(|artist| artist.name.clone())
+            .unwrap_or_default();
+        let origin_country = [album.by_artist.as_ref(), album.publisher.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|artist| artist.founding_location.as_ref())
+            .find_map(|location| location.name.rsplit(',').next().and_then(normalize_country))
+            .map(|c| c.alpha2.to_string());
+        let source_url = album.id.unwrap_or_default();
+
+        AlbumMetadata {
+            provider: "bandcamp".to_string(),
+            source_id: source_url.clone(),
+            compilation: is_various_artists(&artist),
+            artists: credits_from_parts([(artist.as_str(), "")]),
+            artist,
+            album: album.name,
+            genre: album
+                .keywords
+                .map(StringOrList::into_vec)
+                .unwrap_or_default()
+                .into_iter()
+                .take(BANDCAMP_GENRES)
+                .collect::<Vec<_>>()
+                .join(", "),
+            release_date: album
+                .date_published
+                .as_deref()
+                .and_then(bandcamp_release_date)
+                .unwrap_or_default(),
+            source_url,
+            artwork_url: album
+                .image
+                .and_then(|image| image.into_vec().into_iter().next())
+                .unwrap_or_default(),
+            storefront: None,
+            origin_country,
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format: None,
+            format_details: None,
+            track_count: None,
+            tracks: album
+                .track
+                .map(|list| list.items)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| TrackMetadata {
+                    disc_number: 1,
+                    track_number: item.position,
+                    title: item.item.name,
+                    duration_ms: item.item.duration.as_deref().and_then(parse_iso_duration),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<BandcampTralbum> for AlbumMetadata {
+    fn from(tralbum: BandcampTralbum) -> Self {
+        let source_url = tralbum.url.unwrap_or_default();
+        AlbumMetadata {
+            provider: "bandcamp".to_string(),
+            source_id: source_url.clone(),
+            compilation: is_various_artists(&tralbum.artist),
+            artists: credits_from_parts([(tralbum.artist.as_str(), "")]),
+            artist: tralbum.artist,
+            album: tralbum.current.title,
+            genre: String::new(),
+            release_date: tralbum
+                .current
+                .release_date
+                .as_deref()
+                .and_then(bandcamp_release_date)
+                .unwrap_or_default(),
+            source_url,
+            artwork_url: tralbum
+                .art_id
+                .map(|art_id| format!("https://f4.bcbits.com/img/a{}_10.jpg", art_id))
+                .unwrap_or_default(),
+            storefront: None,
+            origin_country: None,
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format: None,
+            format_details: None,
+            track_count: None,
+            tracks: tralbum
+                .trackinfo
+                .into_iter()
+                .enumerate()
+                .map(|(index, track)| TrackMetadata {
+                    disc_number: 1,
+                    track_number: track.track_num.unwrap_or(index as i64 + 1),
+                    title: track.title,
+                    duration_ms: track
+                        .duration
+                        .filter(|seconds| *seconds > 0.0)
+                        .map(|seconds| (seconds * 1000.0).round() as i64),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Bandcamp writes dates like "20 Oct 2023 00:00:00 GMT".
+fn bandcamp_release_date(value: &str) -> Option<String> {
+    let day: Vec<&str> = value.split_whitespace().take(3).collect();
+    NaiveDate::parse_from_str(&day.join(" "), "%d %b %Y")
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .or_else(|| normalize_release_date(value))
+}
+
+/// Parses ISO 8601 durations such as "P00H03M12S" into milliseconds.
+fn parse_iso_duration(duration: &str) -> Option<i64> {
+    let time = duration.strip_prefix('P')?.trim_start_matches('T');
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in time.chars() {
+        match c {
+            'H' => seconds += number.parse::<f64>().ok()? * 3600.0,
+            'M' => seconds += number.parse::<f64>().ok()? * 60.0,
+            'S' => seconds += number.parse::<f64>().ok()?,
+            'T' => continue,
+            _ => {
+                number.push(c);
+                continue;
+            }
+        }
+        number.clear();
+    }
+    Some((seconds * 1000.0).round() as i64).filter(|ms| *ms > 0)
+}
+
+impl From<TrackMetadata> for Track {
+    fn from(track: TrackMetadata) -> Self {
+        Track {
+            disc_number: track.disc_number,
+            track_number: track.track_number,
+            title: track.title,
+            duration_ms: track.duration_ms,
         }
     }
 }
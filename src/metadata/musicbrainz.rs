@@ -0,0 +1,520 @@
+use crate::error::RequestContext;
+use crate::metadata::models::{AlbumMetadata, MusicBrainzRelease, MusicBrainzSearchResponse};
+use crate::metadata::provider::MetadataProvider;
+use crate::{GnedbyError, Result};
+use async_trait::async_trait;
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+const MUSICBRAINZ_API_URL: &str = "https://musicbrainz.org/ws/2";
+
+/// MusicBrainz asks clients to identify themselves with a User-Agent naming the
+/// application, its version and a contact URL.
+const USER_AGENT: &str = concat!(
+    "gnedby/",
+    env!("CARGO_PKG_VERSION"),
+    " ( ",
+    env!("CARGO_PKG_REPOSITORY"),
+    " )"
+);
+
+/// MusicBrainz allows one request per second per client.
+const DEFAULT_RATE_LIMIT: Duration = Duration::from_secs(1);
+
+/// Times a request is retried when MusicBrainz answers 503 (rate limited).
+const MAX_RETRIES: u32 = 3;
+
+const LOOKUP_INCLUDES: &str = "artist-credits+labels+recordings+release-groups+genres";
+const SEARCH_LIMIT: &str = "10";
+
+/// Looks up releases in the MusicBrainz database.
+pub struct MusicBrainzProvider {
+    client: Client,
+    base_url: String,
+    rate_limit: Duration,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl MusicBrainzProvider {
+    pub fn new() -> Self {
+        Self::with_base_url(MUSICBRAINZ_API_URL)
+    }
+
+    /// Uses another API host, e.g. a mirror or a local mock server.
+    pub fn with_base_url(base_url: &str) -> Self {
+        MusicBrainzProvider {
+            client: Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            rate_limit: DEFAULT_RATE_LIMIT,
+            last_request: Mutex::new(None),
+        }
+    }
+
+    /// Sets the minimum time between requests. Mirrors may allow more than
+    /// the one request per second of musicbrainz.org.
+    pub fn with_rate_limit(mut self, rate_limit: Duration) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Waits until the rate limit allows another request.
+    async fn throttle(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            let next = last + self.rate_limit;
+            if next > Instant::now() {
+                sleep(next - Instant::now()).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let url = format!("{}/{}", self.base_url, path);
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            let response = self
+                .client
+                .get(&url)
+                .query(query)
+                .query(&[("fmt", "json")])
+                .send()
+                .await
+                .request_context("Failed to fetch album metadata from MusicBrainz")?;
+
+            let status = response.status();
+            if status == StatusCode::SERVICE_UNAVAILABLE && attempt < MAX_RETRIES {
+                attempt += 1;
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(self.rate_limit);
+                sleep(retry_after).await;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(GnedbyError::from_status(
+                    status,
+                    format!("MusicBrainz API returned error status: {}", status),
+                ));
+            }
+
+            let body = response
+                .json()
+                .await
+                .request_context("Failed to parse MusicBrainz API response")?;
+            return Ok(body);
+        }
+    }
+
+    async fn search_releases(&self, query: &[(&str, &str)]) -> Result<Vec<AlbumMetadata>> {
+        let response: MusicBrainzSearchResponse = self.request("release", query).await?;
+        Ok(response
+            .releases
+            .into_iter()
+            .map(AlbumMetadata::from)
+            .collect())
+    }
+}
+
+impl Default for MusicBrainzProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for MusicBrainzProvider {
+    fn name(&self) -> &'static str {
+        "musicbrainz"
+    }
+
+    fn id_from_url(&self, url: &str) -> Option<String> {
+        musicbrainz_release_id_from_url(url)
+    }
+
+    async fn lookup(&self, id: &str) -> Result<AlbumMetadata> {
+        if !is_mbid(id) {
+            return Err(GnedbyError::Validation(format!(
+                "Not a MusicBrainz release ID: {}",
+                id
+            )));
+        }
+        let release: MusicBrainzRelease = self
+            .request(&format!("release/{}", id), &[("inc", LOOKUP_INCLUDES)])
+            .await
+            .map_err(|e| match e {
+                GnedbyError::NotFound(_) => {
+                    GnedbyError::NotFound(format!("No release found with ID: {}", id))
+                }
+                e => e,
+            })?;
+        Ok(AlbumMetadata::from(release))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<AlbumMetadata>> {
+        // dismax searches release title, artist and other fields as plain text,
+        // so the query needs no Lucene escaping.
+        self.search_releases(&[
+            ("query", query),
+            ("dismax", "true"),
+            ("limit", SEARCH_LIMIT),
+        ])
+        .await
+    }
+
+    async fn lookup_barcode(&self, barcode: &str) -> Result<Option<AlbumMetadata>> {
+        let barcode: String = barcode.chars().filter(char::is_ascii_digit).collect();
+        if barcode.is_empty() {
+            return Ok(None);
+        }
+        let query = format!("barcode:{}", barcode);
+        let Some(release) = self
+            .search_releases(&[("query", &query), ("limit", "1")])
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Ok(None);
+        };
+        // Search results carry no track list, so fetch the full release.
+        self.lookup(&release.source_id).await.map(Some)
+    }
+}
+
+/// Extracts the release MBID from a URL like
+/// `https://musicbrainz.org/release/<mbid>`.
+pub fn musicbrainz_release_id_from_url(url: &str) -> Option<String> {
+    if !url.contains("musicbrainz.org/") {
+        return None;
+    }
+    let path = url.split(['?', '#']).next()?;
+    let mut segments = path.split('/').skip_while(|segment| *segment != "release");
+    segments.next()?;
+    segments
+        .next()
+        .filter(|id| is_mbid(id))
+        .map(str::to_lowercase)
+}
+
+/// MusicBrainz ids are UUIDs, e.g. `3f1c4e7e-2ee0-4a52-a0f1-1e1f4a7f0c7b`.
+fn is_mbid(id: &str) -> bool {
+    let groups: Vec<&str> = id.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{ArtistCredit, ArtistRole};
+    use crate::ErrorKind;
+    use mockito::{Matcher, Mock, Server, ServerGuard};
+    use serde_json::{json, Value};
+    use std::time::Instant;
+
+    const MBID: &str = "0c1e3f6a-5b2d-4c8e-9f7a-1d2b3c4d5e6f";
+
+    fn release() -> Value {
+        json!({
+            "id": MBID,
+            "title": "Palette",
+            "date": "2017-04-21",
+            "country": "KR",
+            "barcode": "8804775079545",
+            "artist-credit": [
+                { "name": "IU", "joinphrase": " feat. " },
+                { "name": "G-DRAGON", "joinphrase": "" }
+            ],
+            "label-info": [
+                { "catalog-number": "L200001354", "label": { "name": "LOEN Entertainment" } }
+            ],
+            "media": [
+                {
+                    "position": 1,
+                    "format": "CD",
+                    "tracks": [
+                        { "position": 1, "number": "1", "title": "이 지금", "length": 208000 },
+                        { "position": 2, "number": "2", "title": "Palette", "length": 217000 }
+                    ]
+                },
+                {
+                    "position": 2,
+                    "tracks": [
+                        { "position": 1, "number": "1", "title": "Bonus", "length": null }
+                    ]
+                }
+            ],
+            "genres": [],
+            "release-group": {
+                "genres": [
+                    { "name": "ballad", "count": 1 },
+                    { "name": "k-pop", "count": 4 }
+                ]
+            },
+            "cover-art-archive": { "front": true }
+        })
+    }
+
+    fn provider(server: &ServerGuard) -> MusicBrainzProvider {
+        MusicBrainzProvider::with_base_url(&format!("{}/ws/2", server.url()))
+            .with_rate_limit(Duration::ZERO)
+    }
+
+    fn json_mock(server: &mut ServerGuard, path: &str, status: usize, body: Value) -> Mock {
+        server
+            .mock("GET", path)
+            .match_query(Matcher::Any)
+            .with_status(status)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+    }
+
+    async fn mock_lookup(server: &mut ServerGuard) -> Mock {
+        lookup_mock(server).create_async().await
+    }
+
+    fn lookup_mock(server: &mut ServerGuard) -> Mock {
+        json_mock(server, &format!("/ws/2/release/{}", MBID), 200, release())
+            .match_query(Matcher::UrlEncoded("fmt".into(), "json".into()))
+            .match_header(
+                "user-agent",
+                Matcher::Regex(r"^gnedby/\S+ \( https?://.+ \)$".into()),
+            )
+    }
+
+    #[tokio::test]
+    async fn lookup_maps_release() {
+        let mut server = Server::new_async().await;
+        mock_lookup(&mut server).await;
+
+        let metadata = provider(&server).lookup(MBID).await.unwrap();
+        assert_eq!(metadata.provider, "musicbrainz");
+        assert_eq!(metadata.source_id, MBID);
+        assert_eq!(metadata.artist, "IU feat. G-DRAGON");
+        assert_eq!(
+            metadata.artists,
+            [
+                ArtistCredit::new("IU", ArtistRole::Primary),
+                ArtistCredit::new("G-DRAGON", ArtistRole::Featuring),
+            ]
+        );
+        assert_eq!(metadata.album, "Palette");
+        assert_eq!(metadata.genre, "k-pop, ballad");
+        assert_eq!(metadata.release_date, "2017-04-21");
+        assert_eq!(metadata.origin_country.as_deref(), Some("KR"));
+        assert_eq!(metadata.label.as_deref(), Some("LOEN Entertainment"));
+        assert_eq!(metadata.catalog_number.as_deref(), Some("L200001354"));
+        assert_eq!(metadata.barcode.as_deref(), Some("8804775079545"));
+        assert_eq!(
+            metadata.source_url,
+            format!("https://musicbrainz.org/release/{}", MBID)
+        );
+        assert_eq!(
+            metadata.artwork_url,
+            format!("https://coverartarchive.org/release/{}/front-500", MBID)
+        );
+        assert!(!metadata.compilation);
+
+        let tracks: Vec<_> = metadata
+            .tracks
+            .iter()
+            .map(|t| {
+                (
+                    t.disc_number,
+                    t.track_number,
+                    t.title.as_str(),
+                    t.duration_ms,
+                )
+            })
+            .collect();
+        assert_eq!(
+            tracks,
+            [
+                (1, 1, "이 지금", Some(208000)),
+                (1, 2, "Palette", Some(217000)),
+                (2, 1, "Bonus", None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_returns_releases() {
+        let mut server = Server::new_async().await;
+        json_mock(
+            &mut server,
+            "/ws/2/release",
+            200,
+            json!({ "count": 1, "releases": [release()] }),
+        )
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("query".into(), "iu palette".into()),
+            Matcher::UrlEncoded("dismax".into(), "true".into()),
+        ]))
+        .create_async()
+        .await;
+
+        let results = provider(&server).search("iu palette").await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].album, "Palette");
+        assert_eq!(results[0].source_id, MBID);
+    }
+
+    #[tokio::test]
+    async fn barcode_lookup_fetches_full_release() {
+        let mut server = Server::new_async().await;
+        let mut search_result = release();
+        search_result["media"] = json!([{ "format": "CD", "track-count": 2 }]);
+        json_mock(
+            &mut server,
+            "/ws/2/release",
+            200,
+            json!({ "count": 1, "releases": [search_result] }),
+        )
+        .match_query(Matcher::UrlEncoded(
+            "query".into(),
+            "barcode:8804775079545".into(),
+        ))
+        .create_async()
+        .await;
+        mock_lookup(&mut server).await;
+
+        let metadata = provider(&server)
+            .lookup_barcode("8 804775 079545")
+            .await
+            .unwrap()
+            .expect("release for barcode");
+        assert_eq!(metadata.source_id, MBID);
+        assert_eq!(metadata.tracks.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn unknown_barcode_is_none() {
+        let mut server = Server::new_async().await;
+        json_mock(
+            &mut server,
+            "/ws/2/release",
+            200,
+            json!({ "count": 0, "releases": [] }),
+        )
+        .create_async()
+        .await;
+
+        let metadata = provider(&server)
+            .lookup_barcode("0000000000000")
+            .await
+            .unwrap();
+        assert!(metadata.is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_release_is_not_found() {
+        let mut server = Server::new_async().await;
+        json_mock(
+            &mut server,
+            &format!("/ws/2/release/{}", MBID),
+            404,
+            json!({ "error": "Not Found" }),
+        )
+        .create_async()
+        .await;
+
+        let error = provider(&server).lookup(MBID).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn partial_dates_are_completed() {
+        let date = |date: &str| {
+            let mut release = release();
+            release["date"] = json!(date);
+            let release: MusicBrainzRelease = serde_json::from_value(release).unwrap();
+            AlbumMetadata::from(release).release_date
+        };
+        assert_eq!(date("2017"), "2017-01-01");
+        assert_eq!(date("2017-04"), "2017-04-01");
+        assert_eq!(date(""), "");
+    }
+
+    #[tokio::test]
+    async fn invalid_id_is_rejected_without_a_request() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let error = provider(&server).lookup("not-an-mbid").await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Validation);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn retries_when_rate_limited_by_server() {
+        let mut server = Server::new_async().await;
+        let unavailable = server
+            .mock("GET", format!("/ws/2/release/{}", MBID).as_str())
+            .match_query(Matcher::Any)
+            .with_status(503)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let lookup = lookup_mock(&mut server).expect(1).create_async().await;
+
+        let metadata = provider(&server).lookup(MBID).await.unwrap();
+        assert_eq!(metadata.album, "Palette");
+        unavailable.assert_async().await;
+        lookup.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn requests_are_spaced_by_rate_limit() {
+        let mut server = Server::new_async().await;
+        mock_lookup(&mut server).await;
+        let provider = MusicBrainzProvider::with_base_url(&format!("{}/ws/2", server.url()))
+            .with_rate_limit(Duration::from_millis(300));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            provider.lookup(MBID).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn release_id_from_url() {
+        assert_eq!(
+            musicbrainz_release_id_from_url(&format!(
+                "https://musicbrainz.org/release/{}/discids",
+                MBID.to_uppercase()
+            )),
+            Some(MBID.to_string())
+        );
+        assert_eq!(
+            musicbrainz_release_id_from_url("https://musicbrainz.org/artist/abc"),
+            None
+        );
+        assert_eq!(
+            musicbrainz_release_id_from_url("https://music.apple.com/album/1"),
+            None
+        );
+    }
+}
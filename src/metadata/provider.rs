@@ -0,0 +1,307 @@
+use crate::config::MetadataConfig;
+use crate::db::Album;
+use crate::metadata::apple::AppleMusicProvider;
+use crate::metadata::bandcamp::BandcampProvider;
+use crate::metadata::discogs::DiscogsProvider;
+use crate::metadata::models::AlbumMetadata;
+use crate::metadata::musicbrainz::MusicBrainzProvider;
+use crate::{ErrorKind, GnedbyError, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A source of album metadata, such as Apple Music.
+#[async_trait]
+pub trait MetadataProvider: Send + Sync {
+    /// Short name used with `gnedby add --provider` and in the config, e.g. "apple".
+    fn name(&self) -> &'static str;
+
+    /// Extracts this provider's album id from one of its album URLs.
+    fn id_from_url(&self, _url: &str) -> Option<String> {
+        None
+    }
+
+    /// Fetches an album, with its tracks, by the provider's own id.
+    async fn lookup(&self, id: &str) -> Result<AlbumMetadata>;
+
+    /// Finds albums matching free text such as "artist album". Results may
+    /// come without tracks; `lookup` the chosen one to get them.
+    async fn search(&self, query: &str) -> Result<Vec<AlbumMetadata>>;
+
+    /// Finds the album with a UPC or EAN barcode, if the provider knows it.
+    async fn lookup_barcode(&self, barcode: &str) -> Result<Option<AlbumMetadata>>;
+}
+
+/// Every provider gnedby knows about, by name.
+pub const PROVIDER_NAMES: &[&str] = &["apple", "musicbrainz", "discogs", "bandcamp"];
+
+/// The enabled metadata providers, in the configured order.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn MetadataProvider>>,
+    default_provider: Option<String>,
+}
+
+impl ProviderRegistry {
+    /// An empty registry, for callers that register their own providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the providers enabled in `config`.
+    pub fn from_config(config: &MetadataConfig) -> Result<Self> {
+        let mut registry = ProviderRegistry::new();
+        for name in &config.providers {
+            let provider: Arc<dyn MetadataProvider> = match name.as_str() {
+                "apple" => Arc::new(
+                    AppleMusicProvider::new()
+                        .with_storefront(config.apple_store.clone())
+                        .with_lang(config.apple_lang.clone())
+                        .with_artwork_size(config.artwork_size),
+                ),
+                "musicbrainz" => Arc::new(MusicBrainzProvider::new()),
+                "discogs" => Arc::new(DiscogsProvider::new(config.discogs_token.clone())),
+                "bandcamp" => Arc::new(BandcampProvider::new()),
+                _ => return Err(unknown_provider(name)),
+            };
+            registry.register(provider);
+        }
+        registry.default_provider = Some(config.default_provider.clone());
+        Ok(registry)
+    }
+
+    /// Adds a provider, replacing an existing one with the same name.
+    pub fn register(&mut self, provider: Arc<dyn MetadataProvider>) {
+        match self
+            .providers
+            .iter_mut()
+            .find(|existing| existing.name() == provider.name())
+        {
+            Some(existing) => *existing = provider,
+            None => self.providers.push(provider),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn MetadataProvider>> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+            .cloned()
+            .ok_or_else(|| unknown_provider(name))
+    }
+
+    /// Picks the provider for an album id or URL given to `gnedby add`: the
+    /// first enabled provider that recognizes the URL, otherwise the default
+    /// provider with the input as its id.
+    pub fn resolve(&self, input: &str) -> Result<(Arc<dyn MetadataProvider>, String)> {
+        for provider in &self.providers {
+            if let Some(id) = provider.id_from_url(input) {
+                return Ok((provider.clone(), id));
+            }
+        }
+        Ok((self.default_provider()?, input.to_string()))
+    }
+
+    /// Looks a stored album up again at the provider it was added from, in the
+    /// Apple Music storefront it was added from rather than the configured one.
+    /// Returns `None` for albums that were not added from a provider.
+    pub async fn lookup_album(&self, album: &Album) -> Result<Option<AlbumMetadata>> {
+        let (Some(provider), Some(source_id)) = (&album.provider, &album.source_id) else {
+            return Ok(None);
+        };
+        let id = source_lookup_id(provider, source_id, album.storefront.as_deref());
+        self.get(provider)?.lookup(&id).await.map(Some)
+    }
+
+    /// Looks a barcode up with `preferred` first, if given, then with every
+    /// other enabled provider in order, returning the first match. A provider
+    /// that fails is skipped; its error is returned only if none matched. A
+    /// provider that is not set up, such as Discogs without a token, is
+    /// skipped without an error.
+    pub async fn lookup_barcode(
+        &self,
+        barcode: &str,
+        preferred: Option<&Arc<dyn MetadataProvider>>,
+    ) -> Result<Option<AlbumMetadata>> {
+        let preferred_name = preferred.map(|provider| provider.name());
+        let chain = preferred.into_iter().chain(
+            self.providers
+                .iter()
+                .filter(|provider| Some(provider.name()) != preferred_name),
+        );
+
+        let mut error = None;
+        for provider in chain {
+            match provider.lookup_barcode(barcode).await {
+                Ok(Some(mut metadata)) => {
+                    if metadata.barcode.is_none() {
+                        metadata.barcode = Some(barcode.to_string());
+                    }
+                    return Ok(Some(metadata));
+                }
+                Ok(None) => {}
+                Err(e) if e.kind() == ErrorKind::Auth => {}
+                Err(e) => error = Some(e),
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    /// The provider used when none is named, or the first enabled one.
+    pub fn default_provider(&self) -> Result<Arc<dyn MetadataProvider>> {
+        match &self.default_provider {
+            Some(name) => self.get(name),
+            None => {
+                self.providers.first().cloned().ok_or_else(|| {
+                    GnedbyError::Validation("No metadata provider is enabled".into())
+                })
+            }
+        }
+    }
+
+    /// Enabled providers in order.
+    pub fn providers(&self) -> impl Iterator<Item = &Arc<dyn MetadataProvider>> {
+        self.providers.iter()
+    }
+}
+
+/// Id to look a provider's album up by. Apple ids carry the storefront, so a
+/// lookup uses the store the album came from rather than the configured one.
+pub(crate) fn source_lookup_id(
+    provider: &str,
+    source_id: &str,
+    storefront: Option<&str>,
+) -> String {
+    match storefront {
+        Some(storefront) if provider == "apple" && !source_id.contains('/') => {
+            format!("{}/{}", storefront.to_lowercase(), source_id)
+        }
+        _ => source_id.to_string(),
+    }
+}
+
+fn unknown_provider(name: &str) -> GnedbyError {
+    if PROVIDER_NAMES.contains(&name) {
+        GnedbyError::Validation(format!(
+            "Provider \"{}\" is not enabled. Enable it with `gnedby provider config set providers`.",
+            name
+        ))
+    } else {
+        GnedbyError::Validation(format!(
+            "Unknown provider \"{}\". Available providers: {}",
+            name,
+            PROVIDER_NAMES.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArtistCredit, Format};
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    fn album(storefront: Option<&str>) -> Album {
+        Album {
+            id: Some(1),
+            artist: "IU".to_string(),
+            album: "Palette".to_string(),
+            genre: "K-Pop".to_string(),
+            release_date: "2017-04-21".to_string(),
+            format: Format::Cd,
+            source_url: "https://music.apple.com/kr/album/palette/1211498223".to_string(),
+            storefront: storefront.map(str::to_string),
+            origin_country: None,
+            artwork_url: String::new(),
+            provider: Some("apple".to_string()),
+            source_id: Some("1211498223".to_string()),
+            label: None,
+            catalog_number: None,
+            barcode: None,
+            format_details: None,
+            artists: ArtistCredit::parse("IU"),
+            compilation: false,
+            genres: Vec::new(),
+            edited_fields: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn apple_ids_carry_the_storefront() {
+        assert_eq!(source_lookup_id("apple", "123", Some("KR")), "kr/123");
+        assert_eq!(source_lookup_id("apple", "jp/123", Some("KR")), "jp/123");
+        assert_eq!(source_lookup_id("apple", "123", None), "123");
+        assert_eq!(source_lookup_id("discogs", "123", Some("KR")), "123");
+    }
+
+    #[tokio::test]
+    async fn barcode_lookup_skips_unconfigured_providers() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/ws/2/release")
+            .match_query(Matcher::Any)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "releases": [] }).to_string())
+            .create_async()
+            .await;
+
+        let mut registry = ProviderRegistry::new();
+        registry.register(Arc::new(DiscogsProvider::with_base_url(
+            "http://127.0.0.1:9",
+            None,
+        )));
+        registry.register(Arc::new(
+            MusicBrainzProvider::with_base_url(&format!("{}/ws/2", server.url()))
+                .with_rate_limit(std::time::Duration::ZERO),
+        ));
+        let result = registry.lookup_barcode("8804775079545", None).await;
+        mock.assert_async().await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn lookup_album_keeps_stored_storefront() {
+        let mut server = Server::new_async().await;
+        let body = json!({
+            "results": [{
+                "wrapperType": "collection",
+                "collectionId": 1211498223,
+                "artistName": "IU",
+                "collectionName": "Palette",
+                "primaryGenreName": "K-Pop",
+                "releaseDate": "2017-04-21T07:00:00Z",
+                "collectionViewUrl": "https://music.apple.com/kr/album/palette/1211498223",
+                "artworkUrl100": "https://is1-ssl.mzstatic.com/image/thumb/a/100x100bb.jpg",
+                "country": "KOR"
+            }]
+        });
+        let mock = server
+            .mock("GET", "/lookup")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), "1211498223".into()),
+                Matcher::UrlEncoded("country".into(), "kr".into()),
+            ]))
+            .with_body(body.to_string())
+            .create_async()
+            .await;
+
+        let mut registry = ProviderRegistry::new();
+        registry.register(Arc::new(
+            AppleMusicProvider::with_base_url(&server.url()).with_storefront(Some("US".into())),
+        ));
+        let metadata = registry
+            .lookup_album(&album(Some("KR")))
+            .await
+            .unwrap()
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(metadata.storefront.as_deref(), Some("KR"));
+
+        let mut manual = album(None);
+        manual.provider = None;
+        assert!(registry.lookup_album(&manual).await.unwrap().is_none());
+    }
+}
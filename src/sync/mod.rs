@@ -1,16 +1,20 @@
+use crate::backup::{auto_backup, replace_database};
 use crate::config::{load_sync_config, save_sync_config};
 use crate::db::calculate_db_hash;
 use crate::db::get_db_path;
 use crate::db::models::Album;
-use crate::db::operations::Database;
+use crate::db::operations::{checkpoint_database, remove_wal_files, Database};
 use std::collections::HashMap;
 
-use anyhow::{Context, Result};
+use crate::error::Context;
+use crate::error::RequestContext;
+use crate::{GnedbyError, Result};
 use chrono::Utc;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,7 +23,9 @@ struct SyncMetadata {
     last_sync: String,
 }
 
-async fn get_remote_database(storage_url: &str, token: &str) -> Result<Vec<Album>> {
+/// Downloads the remote database into a temporary file next to the local one
+/// and returns its path. The caller removes it with `remove_download`.
+async fn download_database(storage_url: &str, token: &str) -> Result<PathBuf> {
     let db_url = format!("{}/albums.db", storage_url);
     let client = create_client(token)?;
 
@@ -27,29 +33,46 @@ async fn get_remote_database(storage_url: &str, token: &str) -> Result<Vec<Album
         .get(&db_url)
         .send()
         .await
-        .context("Failed to download remote database")?;
+        .request_context("Failed to download remote database")?;
 
     if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to download database: {}",
-            response.status()
+        return Err(GnedbyError::from_status(
+            response.status(),
+            format!("Failed to download database: {}", response.status()),
         ));
     }
 
     let db_content = response
         .bytes()
         .await
-        .context("Failed to read database content")?;
+        .request_context("Failed to read database content")?;
 
-    let db_path = get_db_path()?;
-    let backup_path = db_path.with_extension("db.temp");
-    fs::write(&backup_path, &db_content).context("Failed to write temporary database file")?;
+    let temp_path = get_db_path()?.with_extension("db.temp");
+    // A log left by an earlier download must not be applied to this file.
+    remove_download(&temp_path)?;
+    fs::write(&temp_path, &db_content).context("Failed to write temporary database file")?;
+    Ok(temp_path)
+}
+
+fn remove_download(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    remove_wal_files(path)
+}
 
-    let remote_db = Database::with_path(&backup_path).await?;
-    let remote_albums = remote_db.get_all_albums().await?;
+async fn get_remote_database(storage_url: &str, token: &str) -> Result<Vec<Album>> {
+    let temp_path = download_database(storage_url, token).await?;
 
-    fs::remove_file(&backup_path).ok();
-    Ok(remote_albums)
+    let remote_albums = match Database::with_path(&temp_path).await {
+        Ok(remote_db) => remote_db.get_all_albums().await,
+        Err(e) => Err(e),
+    };
+
+    remove_download(&temp_path).ok();
+    remote_albums
 }
 
 pub async fn check_sync_status(verbose: bool) -> Result<bool> {
@@ -133,31 +156,12 @@ pub async fn pull_from_remote() -> Result<()> {
     let storage_url = config.storage_url.unwrap();
     let token = config.token.unwrap();
 
-    backup_database()?;
+    auto_backup("pull")?;
 
-    let db_url = format!("{}/albums.db", storage_url);
-    let client = create_client(&token)?;
-
-    let response = client
-        .get(&db_url)
-        .send()
-        .await
-        .context("Failed to download remote database")?;
-
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to download database: {}",
-            response.status()
-        ));
-    }
-
-    let db_content = response
-        .bytes()
-        .await
-        .context("Failed to read database content")?;
-
-    let db_path = get_db_path()?;
-    fs::write(&db_path, &db_content).context("Failed to write database file")?;
+    let temp_path = download_database(&storage_url, &token).await?;
+    let replaced = replace_database(&temp_path);
+    remove_download(&temp_path).ok();
+    replaced?;
 
     println!("Database pulled successfully!");
 
@@ -186,6 +190,7 @@ pub async fn push_to_remote() -> Result<()> {
     let token = config.token.unwrap();
 
     let db_path = get_db_path()?;
+    checkpoint_database(&db_path)?;
     let db_content = fs::read(&db_path).context("Failed to read local database file")?;
 
     let db_url = format!("{}/albums.db", storage_url);
@@ -197,12 +202,12 @@ pub async fn push_to_remote() -> Result<()> {
         .body(db_content)
         .send()
         .await
-        .context("Failed to upload database")?;
+        .request_context("Failed to upload database")?;
 
     if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to upload database: {}",
-            response.status()
+        return Err(GnedbyError::from_status(
+            response.status(),
+            format!("Failed to upload database: {}", response.status()),
         ));
     }
 
@@ -221,12 +226,12 @@ pub async fn push_to_remote() -> Result<()> {
         .json(&metadata)
         .send()
         .await
-        .context("Failed to upload metadata")?;
+        .request_context("Failed to upload metadata")?;
 
     if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to upload metadata: {}",
-            response.status()
+        return Err(GnedbyError::from_status(
+            response.status(),
+            format!("Failed to upload metadata: {}", response.status()),
         ));
     }
 
@@ -246,10 +251,13 @@ async fn get_remote_metadata(storage_url: &str, token: &str) -> Result<SyncMetad
         .get(&metadata_url)
         .send()
         .await
-        .context("Failed to fetch metadata")?;
+        .request_context("Failed to fetch metadata")?;
 
     if response.status().is_success() {
-        let metadata: SyncMetadata = response.json().await.context("Failed to parse metadata")?;
+        let metadata: SyncMetadata = response
+            .json()
+            .await
+            .request_context("Failed to parse metadata")?;
         Ok(metadata)
     } else if response.status().as_u16() == 404 || response.status().as_u16() == 400 {
         println!("No metadata found in the remote storage. It has not been initialized yet.");
@@ -260,32 +268,21 @@ async fn get_remote_metadata(storage_url: &str, token: &str) -> Result<SyncMetad
             last_sync: "never".to_string(),
         })
     } else {
-        Err(anyhow::anyhow!(
-            "Failed to get metadata: {}",
-            response.status()
+        Err(GnedbyError::from_status(
+            response.status(),
+            format!("Failed to get metadata: {}", response.status()),
         ))
     }
 }
 
-fn backup_database() -> Result<()> {
-    let db_path = get_db_path()?;
-
-    if !db_path.exists() {
-        return Ok(());
-    }
-
-    let backup_path = db_path.with_extension("db.bak");
-    fs::copy(&db_path, &backup_path).context("Failed to create database backup")?;
-
-    println!("Created backup at {:?}", backup_path);
-    Ok(())
-}
-
 fn create_client(token: &str) -> Result<Client> {
     let mut headers = HeaderMap::new();
 
     let auth_value = format!("Bearer {}", token);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&auth_value)?);
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&auth_value).context("Invalid API token")?,
+    );
     headers.insert(
         CONTENT_TYPE,
         HeaderValue::from_static("application/octet-stream"),
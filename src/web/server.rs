@@ -1,15 +1,18 @@
-use crate::db::{Album, Database};
-use anyhow::Result;
+use crate::db::{Album, AlbumFilter, Database, Page, Track};
+use crate::{ErrorKind, GnedbyError, Result};
 use askama::Template;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response},
     routing::get,
     Json, Router,
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 struct AlbumView<'a> {
+    id: i64,
     artwork_url: &'a str,
     album: &'a str,
     artist: &'a str,
@@ -23,27 +26,204 @@ struct AlbumsTemplate<'a> {
     albums: Vec<AlbumView<'a>>,
 }
 
-async fn get_albums(State(db): State<Arc<Database>>) -> Json<Vec<Album>> {
-    match db.get_all_albums().await {
-        Ok(albums) => Json(albums),
-        Err(e) => {
-            eprintln!("Error fetching albums: {}", e);
-            Json(Vec::new())
+struct TrackView<'a> {
+    number: String,
+    title: &'a str,
+    duration: String,
+}
+
+#[derive(Template)]
+#[template(path = "album.html")]
+struct AlbumTemplate<'a> {
+    artwork_url: &'a str,
+    album: &'a str,
+    artist: &'a str,
+    genre: &'a str,
+    format_upper: String,
+    release_date_short: String,
+    /// Label, catalog number and format details, when known.
+    release_info: String,
+    tracks: Vec<TrackView<'a>>,
+}
+
+impl IntoResponse for GnedbyError {
+    fn into_response(self) -> Response {
+        let status = match self.kind() {
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Validation => StatusCode::BAD_REQUEST,
+            ErrorKind::Conflict => StatusCode::CONFLICT,
+            ErrorKind::Auth => StatusCode::UNAUTHORIZED,
+            ErrorKind::Network => StatusCode::BAD_GATEWAY,
+            ErrorKind::Storage | ErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status.is_server_error() {
+            eprintln!("Error handling request: {}", self);
+        }
+        let body = serde_json::json!({ "error": self.to_string() });
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Default and maximum number of albums per page of `/api/albums`.
+const DEFAULT_PER_PAGE: u32 = 100;
+const MAX_PER_PAGE: u32 = 1000;
+
+/// Pagination parameters for `/api/albums`. `page` is 1-based; `after` switches to
+/// keyset pagination and continues after the album with that ID.
+#[derive(Deserialize)]
+struct PageQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+    after: Option<i64>,
+}
+
+/// An album as `/api/albums` returns it. `country` repeats the storefront, or
+/// else the origin country, under the field clients written before the two were
+/// split still read.
+#[derive(Serialize)]
+struct AlbumJson {
+    #[serde(flatten)]
+    album: Album,
+    country: String,
+}
+
+impl From<Album> for AlbumJson {
+    fn from(album: Album) -> Self {
+        let country = album
+            .storefront
+            .clone()
+            .or_else(|| album.origin_country.clone())
+            .unwrap_or_default();
+        AlbumJson { album, country }
+    }
+}
+
+async fn get_albums(
+    State(db): State<Arc<Database>>,
+    Query(query): Query<PageQuery>,
+) -> Result<Response> {
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let page_number = query.page.unwrap_or(1).max(1);
+    let page = Page {
+        limit: Some(per_page),
+        offset: match query.after {
+            Some(_) => 0,
+            None => (page_number - 1).saturating_mul(per_page),
+        },
+        after: query.after,
+    };
+
+    let filter = AlbumFilter::default();
+    let (albums, total) = tokio::try_join!(
+        db.list_albums(&filter, None, page),
+        db.count_albums(&filter)
+    )?;
+
+    let mut links = Vec::new();
+    let link = |params: String, rel: &str| format!("</api/albums?{}>; rel=\"{}\"", params, rel);
+    match query.after {
+        Some(_) => {
+            if albums.len() as u32 == per_page {
+                if let Some(last_id) = albums.last().and_then(|album| album.id) {
+                    links.push(link(
+                        format!("after={}&per_page={}", last_id, per_page),
+                        "next",
+                    ));
+                }
+            }
+        }
+        None => {
+            let last_page = (total.max(1) as u32).div_ceil(per_page);
+            let page_link = |number: u32, rel: &str| {
+                link(format!("page={}&per_page={}", number, per_page), rel)
+            };
+            links.push(page_link(1, "first"));
+            if page_number > 1 {
+                links.push(page_link((page_number - 1).min(last_page), "prev"));
+            }
+            if page_number < last_page {
+                links.push(page_link(page_number + 1, "next"));
+            }
+            links.push(page_link(last_page, "last"));
         }
     }
+
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Total-Count", HeaderValue::from(total));
+    if !links.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+            headers.insert(header::LINK, value);
+        }
+    }
+    let albums: Vec<AlbumJson> = albums.into_iter().map(AlbumJson::from).collect();
+    Ok((headers, Json(albums)).into_response())
+}
+
+async fn find_album(db: &Database, id: i64) -> Result<Album> {
+    db.get_album_by_id(id)
+        .await?
+        .ok_or_else(|| GnedbyError::NotFound(format!("Album with ID {} not found", id)))
 }
 
 async fn get_album_by_id(
     State(db): State<Arc<Database>>,
     Path(id): Path<i64>,
-) -> Json<Option<Album>> {
-    match db.get_album_by_id(id).await {
-        Ok(album) => Json(album),
-        Err(e) => {
-            eprintln!("Error fetching album: {}", e);
-            Json(None)
+) -> Result<Json<AlbumJson>> {
+    Ok(Json(find_album(&db, id).await?.into()))
+}
+
+async fn get_album_tracks(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<Track>>> {
+    find_album(&db, id).await?;
+    Ok(Json(db.get_tracks(id).await?))
+}
+
+async fn album_detail(State(db): State<Arc<Database>>, Path(id): Path<i64>) -> Response {
+    let album = match find_album(&db, id).await {
+        Ok(album) => album,
+        Err(GnedbyError::NotFound(_)) => {
+            return (StatusCode::NOT_FOUND, "Album not found").into_response()
         }
-    }
+        Err(e) => return e.into_response(),
+    };
+    let tracks = db.get_tracks(id).await.unwrap_or_default();
+    let multi_disc = tracks.iter().any(|t| t.disc_number > 1);
+
+    let tmpl = AlbumTemplate {
+        artwork_url: album.artwork_url.as_str(),
+        album: album.album.as_str(),
+        artist: album.artist.as_str(),
+        genre: album.genre.as_str(),
+        format_upper: album.format.to_string().to_uppercase(),
+        release_date_short: album.release_date.chars().take(10).collect(),
+        release_info: [
+            album.label.as_deref(),
+            album.catalog_number.as_deref(),
+            album.format_details.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · "),
+        tracks: tracks
+            .iter()
+            .map(|track| TrackView {
+                number: if multi_disc {
+                    format!("{}-{}", track.disc_number, track.track_number)
+                } else {
+                    track.track_number.to_string()
+                },
+                title: track.title.as_str(),
+                duration: track.duration_display(),
+            })
+            .collect(),
+    };
+    Html(tmpl.render().unwrap()).into_response()
 }
 
 async fn index(State(db): State<Arc<Database>>) -> Html<String> {
@@ -51,6 +231,7 @@ async fn index(State(db): State<Arc<Database>>) -> Html<String> {
     let album_views: Vec<AlbumView> = albums
         .iter()
         .map(|album| AlbumView {
+            id: album.id.unwrap_or(0),
             artwork_url: album.artwork_url.as_str(),
             album: album.album.as_str(),
             artist: album.artist.as_str(),
@@ -76,7 +257,9 @@ pub async fn serve() -> Result<()> {
     let app = Router::new()
         .route("/", get(index))
         .route("/api/albums", get(get_albums))
+        .route("/albums/{id}", get(album_detail))
         .route("/api/albums/{id}", get(get_album_by_id))
+        .route("/api/albums/{id}/tracks", get(get_album_tracks))
         .route("/static/style.css", get(style_css))
         .with_state(db);
 